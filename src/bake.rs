@@ -0,0 +1,56 @@
+use std::f32::consts::PI;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+use light;
+use math;
+use render;
+use shader::World;
+use shadow;
+use wavefront;
+
+// ambient_occlusion bakes the ambient occlusion of every vertex of obj.  A depth map of the model
+// is rendered from each of count random directions around it, as a shadow map of a directional
+// light, and each vertex is tested against it.  Directions are weighted by their cosine with the
+// vertex normal, the result is the weighted fraction of directions that reach the vertex, 1 for
+// unoccluded.  Maps are size x size, obj is expected to fit in the unit cube like for shadow maps.
+pub fn ambient_occlusion(renderer: &render::Renderer,
+                         obj: &wavefront::Object,
+                         count: usize,
+                         size: usize)
+                         -> Vec<f32> {
+    let normals = obj.vertex_normals();
+    let mut lit = vec![0.; obj.vertex_count()];
+    let mut total = vec![0.; obj.vertex_count()];
+    let mut rng = XorShiftRng::from_seed([0x0ba4_e0a0, 0x5c3b_1f17, 0x6e6b_2c4d, 0x29a9_51d3]);
+    for i in 0..count {
+        // Uniformly distributed on the unit sphere.
+        let z = rng.gen::<f32>() * 2. - 1.;
+        let phi = rng.gen::<f32>() * 2. * PI;
+        let r = (1. - z * z).sqrt();
+        let dir = math::Vec3f {
+            x: r * phi.cos(),
+            y: r * phi.sin(),
+            z: z,
+        };
+        debug!("AO pass {}/{} from {:?}", i + 1, count, dir);
+
+        let mut world = World::new();
+        world.clear_lights();
+        let sun = world.add_light(light::Light::directional(dir));
+        let mut map = shadow::ShadowMap::new(renderer, &world, sun, obj, math::Vec3f::zero(), size);
+        map.set_pcf_radius(0);
+        for v in 0..obj.vertex_count() {
+            let w = math::dot(normals[v], dir);
+            if w <= 0. {
+                continue;
+            }
+            total[v] += w;
+            lit[v] += w * map.visibility(obj.vertex(v));
+        }
+    }
+    lit.iter()
+        .zip(total.iter())
+        .map(|(&l, &t)| if t > 0. { l / t } else { 1. })
+        .collect()
+}
@@ -0,0 +1,70 @@
+extern crate env_logger;
+extern crate math;
+extern crate rustyrenderer;
+
+use rustyrenderer::*;
+use std::time::{Duration, Instant};
+
+// Times the incremental rasterizer against the reference per-pixel one on the screen space faces
+// of african_head at 1024x1024, the same setup main renders.
+
+static ITERATIONS: usize = 20;
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs() as f64 * 1e3 + d.subsec_nanos() as f64 / 1e6
+}
+
+fn bench<F>(name: &str, tris: &[[math::Vec3f; 3]], w: usize, h: usize, rasterize: F) -> usize
+    where F: Fn(&[math::Vec3f; 3], usize, usize, &mut FnMut(usize, usize, math::Vec3f))
+{
+    let mut fragments = 0;
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        fragments = 0;
+        for tri in tris {
+            rasterize(tri, w, h, &mut |_, _, _| fragments += 1);
+        }
+    }
+    println!("{:>10}: {:8.3} ms/frame {} fragments",
+             name,
+             millis(start.elapsed()) / ITERATIONS as f64,
+             fragments);
+    fragments
+}
+
+fn main() {
+    env_logger::init().unwrap();
+
+    let model = "obj/african_head.obj";
+    let obj = wavefront::Object::read(model).unwrap();
+    let (width, height) = (1024, 1024);
+    let mut world = shader::World::new();
+    world.set_viewport(width / 8, height / 8, 3 * width / 4, 3 * height / 4);
+    world.look_at(math::Vec3f {
+                      x: 1.,
+                      y: 1.,
+                      z: 3.,
+                  },
+                  math::Vec3f::zero(),
+                  math::Vec3f {
+                      x: 0.,
+                      y: 1.,
+                      z: 0.,
+                  });
+    let tris: Vec<[math::Vec3f; 3]> = obj.into_iter()
+        .map(|f| {
+            [world.vp_p_mv.transform(f.vertices[0]),
+             world.vp_p_mv.transform(f.vertices[1]),
+             world.vp_p_mv.transform(f.vertices[2])]
+        })
+        .collect();
+    println!("{}: {} triangles, {} iterations", model, tris.len(), ITERATIONS);
+
+    let reference = bench("reference", &tris, width, height, |tri, w, h, frag| {
+        raster::rasterize_reference(tri, w, h, |x, y, bc| frag(x, y, bc))
+    });
+    let incremental = bench("rasterize", &tris, width, height, |tri, w, h, frag| {
+        raster::rasterize(tri, w, h, |x, y, bc| frag(x, y, bc))
+    });
+    assert_eq!(reference, incremental);
+}
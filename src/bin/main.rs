@@ -1,12 +1,11 @@
 #[macro_use]
 extern crate log;
 extern crate env_logger;
-extern crate imagefmt;
 extern crate rustyrenderer;
 
-use imagefmt::{ColFmt, ColType};
 use rustyrenderer::*;
 use std::path::Path;
+use std::time::Instant;
 
 static LIGHT_DIR: math::Vec3f = math::Vec3f {
     x: 1.,
@@ -26,6 +25,17 @@ static CENTER_DIR: math::Vec3f = math::Vec3f {
     z: 0.,
 };
 
+// Number of threads rendering tiles of the image.
+static THREADS: usize = 4;
+
+// Width and height of the shadow map.
+static SHADOW_SIZE: usize = 2048;
+
+// Number of directions, and size of the depth maps rendered from them, when baking ambient
+// occlusion.
+static AO_DIRECTIONS: usize = 64;
+static AO_SIZE: usize = 512;
+
 static UP_DIR: math::Vec3f = math::Vec3f {
     x: 0.,
     y: 1.,
@@ -37,7 +47,7 @@ fn main() {
 
     // let model = "obj/pal.obj";
     let model = "obj/african_head.obj";
-    let obj = wavefront::Object::read(model).unwrap();
+    let mut obj = wavefront::Object::read(model).unwrap();
     // let obj = wavefront::Object::read().unwrap();
     info!("Loading model {}", obj);
 
@@ -46,20 +56,45 @@ fn main() {
     let mut z_buffer = draw::DepthBuffer::new(width, height);
     let mut world = shader::World::new();
     world.set_viewport(width / 8, height / 8, 3 * width / 4, 3 * height / 4);
-    world.set_light_dir(LIGHT_DIR);
+    world.clear_lights();
+    let sun = world.add_light(light::Light::directional(LIGHT_DIR));
     world.look_at(EYE_DIR, CENTER_DIR, UP_DIR);
     info!("viewport  : {}", world.viewport);
     info!("projection: {}", world.projection);
     info!("model_view: {}", world.model_view);
-    {
-        let mut shdr = shader::GouraudShader::new(&obj, &mut im, &mut z_buffer);
-        for f in &obj {
-            shader::Shader::draw_face(&mut shdr, &world, &f);
-        }
-    }
+    let start = Instant::now();
+    let mut renderer = render::Renderer::new();
+    renderer.set_threads(THREADS);
+    let ao = bake::ambient_occlusion(&renderer, &obj, AO_DIRECTIONS, AO_SIZE);
+    obj.set_ambient_occlusion(ao);
+    let shadow_map = shadow::ShadowMap::new(&renderer,
+                                             &world,
+                                             sun,
+                                             &obj,
+                                             CENTER_DIR,
+                                             SHADOW_SIZE);
+    let mut shdr = shader::PhongShader::new(&obj);
+    shdr.set_shadow_map(&shadow_map);
+    renderer.draw(&world, &obj, &shdr, &mut im, &mut z_buffer);
+    post::Ssao::new().apply(&world, &mut im, &z_buffer);
+    let elapsed = start.elapsed();
+
+    let caption = format!("{}\n{} triangles, {}.{:03}s",
+                          model,
+                          obj.face_count(),
+                          elapsed.as_secs(),
+                          elapsed.subsec_nanos() / 1_000_000);
+    let mut text = font::Text::new(draw::RGB {
+        r: 255,
+        g: 255,
+        b: 255,
+    });
+    text.scale = 2;
+    text.background = Some(draw::RGB { r: 0, g: 0, b: 0 });
+    let (_, text_h) = font::measure(&caption, text.scale);
+    im.draw_text(8, (height - 8 - text_h) as i64, &caption, &text);
 
-    im.flip_y();
     let out_path = Path::new("output.png");
     println!("Saving {}", out_path.display());
-    imagefmt::write(out_path, im.w, im.h, ColFmt::RGB, &im.buf, ColType::Auto).unwrap();
+    im.save(out_path).unwrap();
 }
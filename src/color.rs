@@ -29,3 +29,24 @@ pub fn rand() -> draw::RGB {
         b: random::<u8>(),
     }
 }
+
+// srgb_to_linear decodes an sRGB encoded channel to linear light in [0, 1].
+pub fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// linear_to_srgb encodes linear light, clamped to [0, 1], as an sRGB channel.
+pub fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.max(0.).min(1.);
+    let c = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    };
+    (c * 255. + 0.5) as u8
+}
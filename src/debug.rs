@@ -0,0 +1,209 @@
+// Shaders and passes for inspecting a render, showing intermediate values as colors instead of
+// lighting.  shader::NormalShader shows normals.
+
+use color;
+use draw;
+use math;
+use raster;
+use shader::{Shader, World};
+use wavefront;
+
+// Maps each component of v from [0, 1] to a color channel, clamping values outside the range.
+fn to_rgb(v: math::Vec3f) -> draw::RGB {
+    draw::RGB {
+        r: (v.x.max(0.).min(1.) * 255.) as u8,
+        g: (v.y.max(0.).min(1.) * 255.) as u8,
+        b: (v.z.max(0.).min(1.) * 255.) as u8,
+    }
+}
+
+fn screen_verts(world: &World, f: &wavefront::Face) -> [math::Vec3f; 3] {
+    [world.vp_p_mv.transform(f.vertices[0]),
+     world.vp_p_mv.transform(f.vertices[1]),
+     world.vp_p_mv.transform(f.vertices[2])]
+}
+
+// UvShader colors fragments with their texture coordinates, u in red and v in green.  Coordinates
+// outside [0, 1] are wrapped so tiling is visible.
+#[derive(Clone)]
+pub struct UvShader {
+    // Varying values, written by vertex shader, read by fragment shader
+    // Texture UV at fragment.
+    uvs: [math::Vec3f; 3],
+}
+
+impl UvShader {
+    pub fn new() -> Self {
+        UvShader { uvs: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()] }
+    }
+}
+
+impl Shader for UvShader {
+    fn vertex(&mut self, world: &World, f: &wavefront::Face) -> [math::Vec3f; 3] {
+        self.uvs = f.texcoords;
+        screen_verts(world, f)
+    }
+
+    fn fragment(&self, _world: &World, bc: math::Vec3f) -> Option<draw::RGB> {
+        let uv = self.uvs[0].scale(bc.x) + self.uvs[1].scale(bc.y) + self.uvs[2].scale(bc.z);
+        Some(to_rgb(math::Vec3f {
+            x: uv.x - uv.x.floor(),
+            y: uv.y - uv.y.floor(),
+            z: 0.,
+        }))
+    }
+}
+
+// DepthShader shows the distance from the camera as grayscale, white at near and black at far.
+// Distance is measured along the view direction after the model view transform, so unlike the
+// depth buffer it is linear.
+#[derive(Clone)]
+pub struct DepthShader {
+    near: f32,
+    far: f32,
+
+    // Varying values, written by vertex shader, read by fragment shader
+    // Distance from the camera at fragment.
+    dists: [f32; 3],
+}
+
+impl DepthShader {
+    pub fn new(near: f32, far: f32) -> Self {
+        DepthShader {
+            near: near,
+            far: far,
+            dists: [0.; 3],
+        }
+    }
+}
+
+impl Shader for DepthShader {
+    fn vertex(&mut self, world: &World, f: &wavefront::Face) -> [math::Vec3f; 3] {
+        // The camera looks down -z, from the eye rather than the origin look_at centers on.
+        let eye = world.model_view.transform(world.eye()).z;
+        for i in 0..3 {
+            self.dists[i] = eye - world.model_view.transform(f.vertices[i]).z;
+        }
+        screen_verts(world, f)
+    }
+
+    fn fragment(&self, _world: &World, bc: math::Vec3f) -> Option<draw::RGB> {
+        let d = self.dists[0] * bc.x + self.dists[1] * bc.y + self.dists[2] * bc.z;
+        let v = 1. - (d - self.near) / (self.far - self.near);
+        Some(to_rgb(math::Vec3f { x: v, y: v, z: v }))
+    }
+}
+
+// BarycentricShader colors fragments with their barycentric coordinates, so each face shades from
+// red, green and blue at its corners.
+#[derive(Clone)]
+pub struct BarycentricShader;
+
+impl BarycentricShader {
+    pub fn new() -> Self {
+        BarycentricShader
+    }
+}
+
+impl Shader for BarycentricShader {
+    fn vertex(&mut self, world: &World, f: &wavefront::Face) -> [math::Vec3f; 3] {
+        screen_verts(world, f)
+    }
+
+    fn fragment(&self, _world: &World, bc: math::Vec3f) -> Option<draw::RGB> {
+        Some(to_rgb(bc))
+    }
+}
+
+// FaceIdShader gives every face of an object its own random color.
+#[derive(Clone)]
+pub struct FaceIdShader {
+    // Color of each face, by index.
+    colors: Vec<draw::RGB>,
+
+    // Varying values, written by vertex shader, read by fragment shader
+    // Color of the face.
+    c: draw::RGB,
+}
+
+impl FaceIdShader {
+    pub fn new(obj: &wavefront::Object) -> Self {
+        FaceIdShader {
+            colors: (0..obj.face_count()).map(|_| color::rand()).collect(),
+            c: draw::RGB { r: 0, g: 0, b: 0 },
+        }
+    }
+}
+
+impl Shader for FaceIdShader {
+    fn vertex(&mut self, world: &World, f: &wavefront::Face) -> [math::Vec3f; 3] {
+        self.c = self.colors[f.idx];
+        screen_verts(world, f)
+    }
+
+    fn fragment(&self, _world: &World, _bc: math::Vec3f) -> Option<draw::RGB> {
+        Some(self.c)
+    }
+}
+
+// overdraw_counts returns how many faces of obj cover each pixel of a w x h image, ignoring depth
+// but honoring world's cull mode.  Pixel x,y is at x + y * w.
+pub fn overdraw_counts(world: &World, obj: &wavefront::Object, w: usize, h: usize) -> Vec<u32> {
+    let mut counts = vec![0u32; w * h];
+    for f in obj {
+        let tri = screen_verts(world, &f);
+        if world.culled(&tri) {
+            continue;
+        }
+        raster::rasterize(&tri, w, h, |x, y, _bc| counts[x + y * w] += 1);
+    }
+    counts
+}
+
+// overdraw renders a w x h heatmap of overdraw_counts.  Uncovered pixels are black, then colors
+// run from blue through green and yellow to red at the most covered pixel.
+pub fn overdraw(world: &World, obj: &wavefront::Object, w: usize, h: usize) -> draw::Image {
+    let counts = overdraw_counts(world, obj, w, h);
+    let max = counts.iter().cloned().max().unwrap_or(0);
+    let mut im = draw::Image::new(w, h);
+    if max == 0 {
+        return im;
+    }
+    for y in 0..h {
+        for x in 0..w {
+            let n = counts[x + y * w];
+            if n > 0 {
+                im.set(x, y, heat(n as f32 / max as f32));
+            }
+        }
+    }
+    im
+}
+
+// heat maps t in [0, 1] onto blue, green, yellow, red.
+fn heat(t: f32) -> draw::RGB {
+    let stops = [math::Vec3f {
+                     x: 0.,
+                     y: 0.,
+                     z: 1.,
+                 },
+                 math::Vec3f {
+                     x: 0.,
+                     y: 1.,
+                     z: 0.,
+                 },
+                 math::Vec3f {
+                     x: 1.,
+                     y: 1.,
+                     z: 0.,
+                 },
+                 math::Vec3f {
+                     x: 1.,
+                     y: 0.,
+                     z: 0.,
+                 }];
+    let s = t * (stops.len() - 1) as f32;
+    let i = (s.floor() as usize).min(stops.len() - 2);
+    let f = s - i as f32;
+    to_rgb(stops[i].scale(1. - f) + stops[i + 1].scale(f))
+}
@@ -5,9 +5,15 @@ use std::path;
 use imagefmt;
 use imagefmt::ColFmt;
 
+use color;
+use font;
+use imageio;
 use math;
+use raster;
+use shape;
+use stroke;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub struct RGB {
     pub r: u8,
     pub g: u8,
@@ -20,6 +26,15 @@ impl fmt::Display for RGB {
     }
 }
 
+// RGB with alpha, 0 transparent to 255 opaque.
+#[derive(Copy, Clone, Debug)]
+pub struct RGBA {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
 #[derive(Debug)]
 pub struct DepthBuffer {
     pub w: usize,
@@ -47,7 +62,7 @@ impl DepthBuffer {
         let off = x + y * self.w;
         self.buf[off] = z;
     }
-    pub fn get(&mut self, x: usize, y: usize) -> f32 {
+    pub fn get(&self, x: usize, y: usize) -> f32 {
         if x >= self.w || y >= self.h {
             error!(
                 "Out of bounds get depth {},{} size {}x{}",
@@ -59,49 +74,331 @@ impl DepthBuffer {
         let off = x + y * self.w;
         self.buf[off]
     }
+
+    // sub_buffer returns a copy of the w x h region of the buffer with its corner at x,y.
+    pub fn sub_buffer(&self, x: usize, y: usize, w: usize, h: usize) -> DepthBuffer {
+        let mut sub = DepthBuffer::new(w, h);
+        for row in 0..h {
+            let src = x + (y + row) * self.w;
+            sub.buf[row * w..(row + 1) * w].copy_from_slice(&self.buf[src..src + w]);
+        }
+        sub
+    }
+
+    // blit copies all of src into the buffer with src's corner at x,y.
+    pub fn blit(&mut self, src: &DepthBuffer, x: usize, y: usize) {
+        for row in 0..src.h {
+            let dst = x + (y + row) * self.w;
+            self.buf[dst..dst + src.w].copy_from_slice(&src.buf[row * src.w..(row + 1) * src.w]);
+        }
+    }
+}
+
+// How Texture2D::sample_with filters texels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Filter {
+    // The single texel under the sample, what Texture2D::sample does.
+    Nearest,
+    // Weighted average of the four texels around the sample in the full size texture.
+    Bilinear,
+    // Bilinear samples from the two mipmap levels nearest the texture's scale on screen, blended.
+    Trilinear,
+    // Up to the given number of trilinear samples spread along the direction the texture is most
+    // squashed on screen, from a level picked for the least squashed direction, averaged.  Keeps
+    // textures on surfaces seen edge on sharp where trilinear blurs them.
+    Anisotropic(usize),
+}
+
+// What Texture2D samples outside [0, 1] in u or v.
+#[derive(Copy, Clone, Debug)]
+pub enum Wrap {
+    // Tile the texture.
+    Repeat,
+    // Tile the texture, flipping every other tile so neighboring tiles meet seamlessly.
+    MirroredRepeat,
+    // Repeat the texels on the edge.
+    ClampToEdge,
+    // Use the given color outside the texture.
+    ClampToBorder(RGB),
+}
+
+// wrap maps texel coordinate i, which may be outside a row or column of n texels, to the texel
+// used, or None if wrap says to use the border color.
+fn wrap(wrap: Wrap, i: i64, n: usize) -> Option<usize> {
+    let n = n as i64;
+    match wrap {
+        Wrap::Repeat => Some((((i % n) + n) % n) as usize),
+        Wrap::MirroredRepeat => {
+            let m = ((i % (2 * n)) + 2 * n) % (2 * n);
+            Some(if m < n { m } else { 2 * n - 1 - m } as usize)
+        }
+        Wrap::ClampToEdge => Some(i.max(0).min(n - 1) as usize),
+        Wrap::ClampToBorder(_) => {
+            if i < 0 || i >= n {
+                None
+            } else {
+                Some(i as usize)
+            }
+        }
+    }
+}
+
+// reduce moves texel coordinate t, along a row or column of n texels, into or just around the
+// texture without changing what wrap samples there, so it can be cast to an integer.  NaN samples
+// texel 0 and infinities the far edge or border when clamping.
+fn reduce(wrap: Wrap, t: f32, n: usize) -> f32 {
+    let n = n as f32;
+    if t.is_nan() {
+        return 0.;
+    }
+    let period = match wrap {
+        Wrap::Repeat => n,
+        Wrap::MirroredRepeat => 2. * n,
+        Wrap::ClampToEdge | Wrap::ClampToBorder(_) => return t.max(-1.).min(n),
+    };
+    if t.is_infinite() {
+        return 0.;
+    }
+    // Rounding can leave far out coordinates a little outside the period.
+    (t - (t / period).floor() * period).max(0.).min(period)
+}
+
+// Sampler holds the state used to sample a Texture2D.
+#[derive(Copy, Clone, Debug)]
+pub struct Sampler {
+    pub filter: Filter,
+    pub wrap: Wrap,
+}
+
+impl Sampler {
+    // New sampler filtering with filter, repeating the texture.
+    pub fn new(filter: Filter) -> Self {
+        Sampler {
+            filter: filter,
+            wrap: Wrap::Repeat,
+        }
+    }
 }
 
 // let _pic = imagefmt::read("stars.jpg", ColFmt::BGRA).unwrap();
 pub struct Texture2D {
     pub w: usize,
     pub h: usize,
+    // RGBA texels, images without alpha are loaded opaque.
     pub buf: Vec<u8>,
+    // Mipmap levels after the full size texture, each half the size of the one before down to
+    // 1x1.
+    mips: Vec<MipLevel>,
+}
+
+struct MipLevel {
+    w: usize,
+    h: usize,
+    buf: Vec<u8>,
+}
+
+// Texel colors, RGBA, as floats so filtering doesn't round at every step.
+type Texel = [f32; 4];
+
+fn lerp_texel(a: Texel, b: Texel, t: f32) -> Texel {
+    let mut c = a;
+    for i in 0..4 {
+        c[i] += (b[i] - a[i]) * t;
+    }
+    c
+}
+
+// build_mips returns the mipmap chain of the w x h RGBA image buf, each level a 2x2 box filter of
+// the one before.  Odd sizes round down, repeating the last row or column.
+fn build_mips(w: usize, h: usize, buf: &[u8]) -> Vec<MipLevel> {
+    let mut mips: Vec<MipLevel> = Vec::new();
+    let (mut pw, mut ph) = (w, h);
+    while pw > 1 || ph > 1 {
+        let (nw, nh) = ((pw / 2).max(1), (ph / 2).max(1));
+        let mut next = vec![0; nw * nh * 4];
+        {
+            let prev = match mips.last() {
+                Some(l) => &l.buf[..],
+                None => buf,
+            };
+            for y in 0..nh {
+                for x in 0..nw {
+                    let (x0, y0) = ((2 * x).min(pw - 1), (2 * y).min(ph - 1));
+                    let (x1, y1) = ((2 * x + 1).min(pw - 1), (2 * y + 1).min(ph - 1));
+                    for c in 0..4 {
+                        let sum = prev[(x0 + y0 * pw) * 4 + c] as u32 +
+                                  prev[(x1 + y0 * pw) * 4 + c] as u32 +
+                                  prev[(x0 + y1 * pw) * 4 + c] as u32 +
+                                  prev[(x1 + y1 * pw) * 4 + c] as u32;
+                        next[(x + y * nw) * 4 + c] = ((sum + 2) / 4) as u8;
+                    }
+                }
+            }
+        }
+        mips.push(MipLevel {
+            w: nw,
+            h: nh,
+            buf: next,
+        });
+        pw = nw;
+        ph = nh;
+    }
+    mips
 }
 
 impl Texture2D {
+    // new wraps the w x h RGBA image buf, building its mipmaps.
+    pub fn new(w: usize, h: usize, buf: Vec<u8>) -> Self {
+        let mips = build_mips(w, h, &buf);
+        Texture2D {
+            w: w,
+            h: h,
+            buf: buf,
+            mips: mips,
+        }
+    }
+
     pub fn read<P: AsRef<path::Path>>(filepath: P) -> imagefmt::Result<Self> {
         info!("Reading texture {:?}", filepath.as_ref());
-        let im = try!(imagefmt::read(filepath, ColFmt::RGB));
-        Ok(Texture2D {
-            w: im.w,
-            h: im.h,
-            buf: im.buf,
-        })
+        let im = try!(imagefmt::read(filepath, ColFmt::RGBA));
+        Ok(Texture2D::new(im.w, im.h, im.buf))
     }
 
     // TODO(wathiede): make uv a T : Sample that works for 1, 2 & 3D.
     // TODO(wathiede): make an Output type that returns Grey / RGB / RGBA as appropriate.
+    // sample returns the texel under uv, repeating the texture outside [0, 1].
     pub fn sample(&self, uv: math::Vec3f) -> RGB {
-        let (w, h) = (self.w as f32, self.h as f32);
-        // XXX Images are 0,0 at the upper left corner, but texture maps are 0,0 at the lower left
-        // corner.  Hack for now.
-        // let (x, y) = (((uv.x * w) % w) as usize, ((uv.y * h) % h) as usize);
-        let (x, y) = (((uv.x * w) % w) as usize, (((1. - uv.y) * h) % h) as usize);
-        if x >= self.w || y >= self.h {
-            error!(
-                "Out of bounds set pixel {},{} size {}x{}",
-                x, y, self.w, self.h
-            );
-            return RGB { r: 0, g: 0, b: 0 };
+        let zero = math::Vec3f::zero();
+        self.sample_with(&Sampler::new(Filter::Nearest), uv, zero, zero)
+    }
+
+    // sample_with samples the texture at uv filtered and wrapped as sampler says.  duv_dx and
+    // duv_dy are how much uv changes moving one pixel right and down on screen, they choose the
+    // mipmap level and direction of anisotropy.
+    pub fn sample_with(&self,
+                       sampler: &Sampler,
+                       uv: math::Vec3f,
+                       duv_dx: math::Vec3f,
+                       duv_dy: math::Vec3f)
+                       -> RGB {
+        let c = self.sample_rgba_with(sampler, uv, duv_dx, duv_dy);
+        RGB {
+            r: c.r,
+            g: c.g,
+            b: c.b,
         }
-        let off = (x + y * self.w) * 3;
-        let c = RGB {
-            r: self.buf[off + 0],
-            g: self.buf[off + 1],
-            b: self.buf[off + 2],
+    }
+
+    // sample_rgba_with is sample_with including the texture's alpha.
+    pub fn sample_rgba_with(&self,
+                            sampler: &Sampler,
+                            uv: math::Vec3f,
+                            duv_dx: math::Vec3f,
+                            duv_dy: math::Vec3f)
+                            -> RGBA {
+        // Screen space derivatives in texels of the full size texture.
+        let (w, h) = (self.w as f32, self.h as f32);
+        let (dx, dy) = ((duv_dx.x * w, duv_dx.y * h), (duv_dy.x * w, duv_dy.y * h));
+        let len_x = (dx.0 * dx.0 + dx.1 * dx.1).sqrt();
+        let len_y = (dy.0 * dy.0 + dy.1 * dy.1).sqrt();
+        let t = match sampler.filter {
+            Filter::Nearest => self.nearest(sampler, uv),
+            Filter::Bilinear => self.bilinear(sampler, 0, uv),
+            Filter::Trilinear => self.trilinear(sampler, len_x.max(len_y).log2(), uv),
+            Filter::Anisotropic(max) => {
+                let (major, minor, axis) = if len_x > len_y {
+                    (len_x, len_y, duv_dx)
+                } else {
+                    (len_y, len_x, duv_dy)
+                };
+                let n = (major / minor.max(1e-6)).ceil().max(1.).min(max.max(1) as f32) as usize;
+                let lod = (major / n as f32).log2();
+                let mut sum = [0.; 4];
+                for i in 0..n {
+                    // Spread samples evenly over the pixel's footprint along the major axis.
+                    let offset = (i as f32 + 0.5) / n as f32 - 0.5;
+                    let t = self.trilinear(sampler, lod, uv + axis.scale(offset));
+                    for c in 0..4 {
+                        sum[c] += t[c] / n as f32;
+                    }
+                }
+                sum
+            }
         };
-        // info!("Image.get {} {}", uv, c);
-        c
+        RGBA {
+            r: (t[0] + 0.5) as u8,
+            g: (t[1] + 0.5) as u8,
+            b: (t[2] + 0.5) as u8,
+            a: (t[3] + 0.5) as u8,
+        }
+    }
+
+    // Size and texels of mipmap level, 0 is the full size texture.
+    fn level(&self, level: usize) -> (usize, usize, &[u8]) {
+        if level == 0 {
+            (self.w, self.h, &self.buf)
+        } else {
+            let l = &self.mips[level - 1];
+            (l.w, l.h, &l.buf)
+        }
+    }
+
+    // texel returns texel x,y of level, 0,0 at the upper left corner, wrapped as sampler says.
+    fn texel(&self, sampler: &Sampler, level: usize, x: i64, y: i64) -> Texel {
+        let (w, h, buf) = self.level(level);
+        match (wrap(sampler.wrap, x, w), wrap(sampler.wrap, y, h)) {
+            (Some(x), Some(y)) => {
+                let off = (x + y * w) * 4;
+                [buf[off] as f32, buf[off + 1] as f32, buf[off + 2] as f32, buf[off + 3] as f32]
+            }
+            _ => {
+                let c = match sampler.wrap {
+                    Wrap::ClampToBorder(c) => c,
+                    _ => unreachable!(),
+                };
+                [c.r as f32, c.g as f32, c.b as f32, 255.]
+            }
+        }
+    }
+
+    // nearest returns the texel of the full size texture under uv.
+    fn nearest(&self, sampler: &Sampler, uv: math::Vec3f) -> Texel {
+        // Images are 0,0 at the upper left corner, but texture maps are 0,0 at the lower left
+        // corner.
+        let x = reduce(sampler.wrap, uv.x * self.w as f32, self.w).floor() as i64;
+        let y = reduce(sampler.wrap, (1. - uv.y) * self.h as f32, self.h).floor() as i64;
+        self.texel(sampler, 0, x, y)
+    }
+
+    // trilinear blends bilinear samples of the levels either side of lod, the log2 of texels per
+    // pixel.
+    fn trilinear(&self, sampler: &Sampler, lod: f32, uv: math::Vec3f) -> Texel {
+        let lod = lod.max(0.).min(self.mips.len() as f32);
+        let l = lod.floor() as usize;
+        if l == self.mips.len() {
+            return self.bilinear(sampler, l, uv);
+        }
+        lerp_texel(self.bilinear(sampler, l, uv),
+                   self.bilinear(sampler, l + 1, uv),
+                   lod - l as f32)
+    }
+
+    // bilinear interpolates the four texels of level around uv.
+    fn bilinear(&self, sampler: &Sampler, level: usize, uv: math::Vec3f) -> Texel {
+        let (w, h, _) = self.level(level);
+        // Texel centers are at half integer coordinates.
+        let x = reduce(sampler.wrap, uv.x * w as f32 - 0.5, w);
+        let y = reduce(sampler.wrap, (1. - uv.y) * h as f32 - 0.5, h);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = lerp_texel(self.texel(sampler, level, x0, y0),
+                             self.texel(sampler, level, x0 + 1, y0),
+                             fx);
+        let bottom = lerp_texel(self.texel(sampler, level, x0, y0 + 1),
+                                self.texel(sampler, level, x0 + 1, y0 + 1),
+                                fx);
+        lerp_texel(top, bottom, fy)
     }
 }
 
@@ -133,7 +430,7 @@ impl Image {
         self.buf[off + 1] = c.g;
         self.buf[off + 2] = c.b;
     }
-    pub fn get(&mut self, x: usize, y: usize) -> RGB {
+    pub fn get(&self, x: usize, y: usize) -> RGB {
         if x >= self.w || y >= self.h {
             error!(
                 "Out of bounds set pixel {},{} size {}x{}",
@@ -147,7 +444,7 @@ impl Image {
             g: self.buf[off + 1],
             b: self.buf[off + 2],
         };
-        info!("Image.get {}", c);
+        trace!("Image.get {}", c);
         c
     }
 
@@ -178,8 +475,9 @@ impl Image {
                 }
             }
         };
-        for x in x0..x1 {
-            let t = (x - x0) as f32 / (x1 - x0) as f32;
+        // Both end points are drawn.
+        for x in x0..x1 + 1 {
+            let t = if x1 == x0 { 0. } else { (x - x0) as f32 / (x1 - x0) as f32 };
             let y = y0 as f32 * (1. - t) + y1 as f32 * t;
             let (xs, ys) = if steep {
                 (y as usize, x as usize)
@@ -190,38 +488,206 @@ impl Image {
         }
     }
 
+    // line_aa draws an anti-aliased one pixel wide line from p0 to p1, z is ignored.  See
+    // stroke::Stroke for the coordinate convention.
+    pub fn line_aa(&mut self, p0: &math::Vec3f, p1: &math::Vec3f, c: RGB) {
+        self.stroke(&[*p0, *p1], &stroke::Stroke::new(c));
+    }
+
+    // stroke draws the polyline through points, z is ignored, with style.
+    pub fn stroke(&mut self, points: &[math::Vec3f], style: &stroke::Stroke) {
+        self.paint(&stroke::coverage(points, style, self.bounds()), style.color);
+    }
+
+    // stroke_polygon draws the outline of the polygon points, z is ignored, with style.
+    pub fn stroke_polygon(&mut self, points: &[math::Vec3f], style: &stroke::Stroke) {
+        self.paint(&stroke::coverage_closed(points, style, self.bounds()), style.color);
+    }
+
+    // fill_polygon fills the polygon points, z is ignored, with style.  See shape::FillRule for
+    // self-intersecting polygons and polygons with holes.
+    pub fn fill_polygon(&mut self, points: &[math::Vec3f], style: &shape::Fill) {
+        self.paint(&shape::coverage(points, style, self.bounds()), style.color);
+    }
+
+    // bezier draws the cubic Bézier curve from p[0] to p[3] with control points p[1] and p[2].
+    pub fn bezier(&mut self, p: &[math::Vec3f; 4], style: &stroke::Stroke) {
+        self.stroke(&shape::cubic(p[0], p[1], p[2], p[3]), style);
+    }
+
+    // fill_rect fills the w x h pixels with their corner at x,y.  Pixels off the image are
+    // skipped.
+    pub fn fill_rect(&mut self, x: i64, y: i64, w: usize, h: usize, c: RGB) {
+        for py in y.max(0)..(y + h as i64).min(self.h as i64) {
+            for px in x.max(0)..(x + w as i64).min(self.w as i64) {
+                self.set(px as usize, py as usize, c);
+            }
+        }
+    }
+
+    // stroke_rect outlines the w x h pixels with their corner at x,y.  The outline is centered on
+    // the edge pixels, so a one pixel wide stroke covers exactly them.
+    pub fn stroke_rect(&mut self, x: i64, y: i64, w: usize, h: usize, style: &stroke::Stroke) {
+        if w == 0 || h == 0 {
+            return;
+        }
+        let (x0, y0) = (x as f32 + 0.5, y as f32 + 0.5);
+        let (x1, y1) = (x0 + (w - 1) as f32, y0 + (h - 1) as f32);
+        let p = |x, y| math::Vec3f { x: x, y: y, z: 0. };
+        self.stroke_polygon(&[p(x0, y0), p(x1, y0), p(x1, y1), p(x0, y1)], style);
+    }
+
+    // circle outlines the circle of radius r centered on pixel cx,cy.
+    pub fn circle(&mut self, cx: i64, cy: i64, r: i64, c: RGB) {
+        self.ellipse(cx, cy, r, r, c);
+    }
+
+    // fill_circle fills the circle of radius r centered on pixel cx,cy.
+    pub fn fill_circle(&mut self, cx: i64, cy: i64, r: i64, c: RGB) {
+        self.fill_ellipse(cx, cy, r, r, c);
+    }
+
+    // ellipse outlines the axis aligned ellipse with radii rx and ry centered on pixel cx,cy.
+    pub fn ellipse(&mut self, cx: i64, cy: i64, rx: i64, ry: i64, c: RGB) {
+        shape::ellipse(rx, ry, |x, y| {
+            self.plot(cx + x, cy + y, c);
+            self.plot(cx - x, cy + y, c);
+            self.plot(cx + x, cy - y, c);
+            self.plot(cx - x, cy - y, c);
+        });
+    }
+
+    // fill_ellipse fills the axis aligned ellipse with radii rx and ry centered on pixel cx,cy.
+    pub fn fill_ellipse(&mut self, cx: i64, cy: i64, rx: i64, ry: i64, c: RGB) {
+        shape::ellipse_spans(rx, ry,
+                             |y, w| self.fill_rect(cx - w, cy + y, (2 * w + 1) as usize, 1, c));
+    }
+
+    // draw_text draws text, lines separated by '\n', with style.  The text's bottom edge is at y
+    // and lines are aligned to x, extending up and away from it as the image is y up.  Use
+    // font::measure to place text by its other edges.
+    pub fn draw_text(&mut self, x: i64, y: i64, text: &str, style: &font::Text) {
+        let s = style.scale.max(1);
+        let (w, h) = font::measure(text, s);
+        let left = |lw: usize| match style.align {
+            font::Align::Left => x,
+            font::Align::Center => x - lw as i64 / 2,
+            font::Align::Right => x - lw as i64,
+        };
+        if let Some(bg) = style.background {
+            let p = style.padding;
+            self.fill_rect(left(w) - p as i64, y - p as i64, w + 2 * p, h + 2 * p, bg);
+        }
+        let s = s as i64;
+        for (i, line) in text.lines().enumerate() {
+            let top = y + h as i64 - (i * font::LINE_HEIGHT) as i64 * s;
+            let mut gx = left(font::line_width(line, s as usize));
+            for ch in line.chars() {
+                for (r, bits) in font::glyph(ch).iter().enumerate() {
+                    for c in 0..font::GLYPH_W {
+                        if bits & (1 << (font::GLYPH_W - 1 - c)) != 0 {
+                            self.fill_rect(gx + c as i64 * s,
+                                           top - (r as i64 + 1) * s,
+                                           s as usize,
+                                           s as usize,
+                                           style.color);
+                        }
+                    }
+                }
+                gx += (font::ADVANCE as i64) * s;
+            }
+        }
+    }
+
+    // bounds returns the image's pixels, x_min, y_min, x_max, y_max inclusive, to clip coverage to.
+    fn bounds(&self) -> (i64, i64, i64, i64) {
+        (0, 0, self.w as i64 - 1, self.h as i64 - 1)
+    }
+
+    // plot sets pixel x,y to c if it's on the image.
+    fn plot(&mut self, x: i64, y: i64, c: RGB) {
+        if x >= 0 && y >= 0 && x < self.w as i64 && y < self.h as i64 {
+            self.set(x as usize, y as usize, c);
+        }
+    }
+
+    // paint blends c into the pixels covered by cov.
+    fn paint(&mut self, cov: &stroke::Coverage, c: RGB) {
+        for y in cov.y.max(0)..(cov.y + cov.h as i64).min(self.h as i64) {
+            for x in cov.x.max(0)..(cov.x + cov.w as i64).min(self.w as i64) {
+                let a = cov.get(x, y);
+                if a > 0. {
+                    self.blend(x as usize, y as usize, c, a);
+                }
+            }
+        }
+    }
+
+    // blend mixes c into pixel x,y by coverage, 0 leaves the pixel unchanged and 1 replaces it.
+    // Mixing is done in linear space so partially covered pixels have the right brightness.
+    pub fn blend(&mut self, x: usize, y: usize, c: RGB, coverage: f32) {
+        let dst = self.get(x, y);
+        let mix = |d: u8, s: u8| {
+            color::linear_to_srgb(color::srgb_to_linear(s) * coverage +
+                                  color::srgb_to_linear(d) * (1. - coverage))
+        };
+        self.set(x,
+                 y,
+                 RGB {
+                     r: mix(dst.r, c.r),
+                     g: mix(dst.g, c.g),
+                     b: mix(dst.b, c.b),
+                 });
+    }
+
     // TODO(wathiede): handle the z_buffer more elegantly, maybe create a type Renderer that wraps
     // an Image and DepthBuffer and implements triangle?
     pub fn triangle(&mut self, tri: &[math::Vec3f; 3], z_buffer: &mut DepthBuffer, c: RGB) {
-        let ref v0 = tri[0].to_vec2i();
-        let ref v1 = tri[1].to_vec2i();
-        let ref v2 = tri[2].to_vec2i();
-        use std::cmp::{max, min};
-        let (x_min, x_max) = (min(min(v0.x, v1.x), v2.x), max(max(v0.x, v1.x), v2.x));
-        let (y_min, y_max) = (min(min(v0.y, v1.y), v2.y), max(max(v0.y, v1.y), v2.y));
-        for y in y_min..y_max {
-            for x in x_min..x_max {
-                let bc = math::barycentric(
-                    tri,
-                    math::Vec3f {
-                        x: x as f32,
-                        y: y as f32,
-                        z: 0.,
-                    },
-                );
-                if bc.x < 0. || bc.y < 0. || bc.z < 0. {
-                    continue;
-                }
-                let (sx, sy) = (x as usize, y as usize);
-                let z = tri[0].z * bc.x + tri[1].z * bc.y + tri[2].z * bc.z;
-                if z_buffer.get(sx, sy) < z {
-                    z_buffer.set(sx, sy, z);
-                    self.set(sx, sy, c);
-                }
+        let (w, h) = (self.w, self.h);
+        raster::rasterize(tri, w, h, |sx, sy, bc| {
+            let z = tri[0].z * bc.x + tri[1].z * bc.y + tri[2].z * bc.z;
+            if z_buffer.get(sx, sy) < z {
+                z_buffer.set(sx, sy, z);
+                self.set(sx, sy, c);
             }
+        });
+    }
+
+    // sub_image returns a copy of the w x h region of the image with its corner at x,y.
+    pub fn sub_image(&self, x: usize, y: usize, w: usize, h: usize) -> Image {
+        let mut sub = Image::new(w, h);
+        for row in 0..h {
+            let src = (x + (y + row) * self.w) * 3;
+            sub.buf[row * w * 3..(row + 1) * w * 3].copy_from_slice(&self.buf[src..src + w * 3]);
+        }
+        sub
+    }
+
+    // blit copies all of src into the image with src's corner at x,y.
+    pub fn blit(&mut self, src: &Image, x: usize, y: usize) {
+        let stride = src.w * 3;
+        for row in 0..src.h {
+            let dst = (x + (y + row) * self.w) * 3;
+            self.buf[dst..dst + stride].copy_from_slice(&src.buf[row * stride..(row + 1) * stride]);
         }
     }
 
+    // save writes the image to path, in the format named by its extension.  See imageio::Format
+    // for the formats supported.
+    pub fn save<P: AsRef<path::Path>>(&self, path: P) -> imageio::Result<()> {
+        imageio::save_rgb(path, self.w, self.h, &self.buf)
+    }
+
+    // load reads the image at path, in the format named by its extension.
+    pub fn load<P: AsRef<path::Path>>(path: P) -> imageio::Result<Image> {
+        let (w, h, buf) = try!(imageio::load_rgb(path));
+        Ok(Image {
+            w: w,
+            h: h,
+            buf: buf,
+        })
+    }
+
     pub fn flip_y(&mut self) {
         for y in 0..self.h / 2 {
             for x in 0..self.w {
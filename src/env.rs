@@ -0,0 +1,413 @@
+use std::f32;
+use std::f32::consts::PI;
+use std::path;
+
+use imagefmt;
+
+use color;
+use draw;
+use hdr;
+use imageio;
+use math;
+use shader::{self, Shader, World};
+use wavefront;
+
+// Prefiltered levels stop halving once they'd be narrower than this.
+const MIN_LEVEL_WIDTH: usize = 8;
+// Spherical harmonics are projected from the first level at most this wide.
+const SH_WIDTH: usize = 64;
+
+// EnvironmentMap is light arriving from infinitely far away in every direction, stored as an
+// equirectangular texture.  It provides the background, mirror and glossy reflections from a
+// chain of successively blurred half size levels, and diffuse irradiance from a 9 coefficient
+// spherical harmonics projection.  Directions are in model space, +y up, and colors returned
+// are linear.  Maps are kept as linear floats, so light above 1 from HDR images is preserved.
+pub struct EnvironmentMap {
+    // Level 0 is the map as loaded, each following level is half the size and blurrier.
+    levels: Vec<Level>,
+    // Radiance projected onto the first three bands of real spherical harmonics, red, green and
+    // blue in x, y and z.
+    sh: [math::Vec3f; 9],
+}
+
+// Maps a direction to equirectangular texture coordinates, u around the y axis starting at -z,
+// v from the bottom pole to the top.
+fn direction_to_uv(d: math::Vec3f) -> math::Vec3f {
+    let d = d.normalize();
+    math::Vec3f {
+        x: 0.5 + d.x.atan2(-d.z) / (2. * PI),
+        y: 0.5 + d.y.max(-1.).min(1.).asin() / PI,
+        z: 0.,
+    }
+}
+
+// Inverse of direction_to_uv.
+fn uv_to_direction(u: f32, v: f32) -> math::Vec3f {
+    let phi = (u - 0.5) * 2. * PI;
+    let theta = (v - 0.5) * PI;
+    math::Vec3f {
+        x: theta.cos() * phi.sin(),
+        y: theta.sin(),
+        z: -theta.cos() * phi.cos(),
+    }
+}
+
+// Level is an equirectangular image of linear light, red, green and blue in x, y and z, with its
+// top row first.
+struct Level {
+    w: usize,
+    h: usize,
+    buf: Vec<math::Vec3f>,
+}
+
+impl Level {
+    // Decodes the sRGB texture t.
+    fn from_texture(t: &draw::Texture2D) -> Self {
+        let buf = t.buf
+            .chunks(4)
+            .map(|c| {
+                math::Vec3f {
+                    x: color::srgb_to_linear(c[0]),
+                    y: color::srgb_to_linear(c[1]),
+                    z: color::srgb_to_linear(c[2]),
+                }
+            })
+            .collect();
+        Level {
+            w: t.w,
+            h: t.h,
+            buf: buf,
+        }
+    }
+
+    // Copies the color of im, which is stored bottom row first.
+    fn from_hdr(im: &hdr::HdrImage) -> Self {
+        let mut buf = Vec::with_capacity(im.w * im.h);
+        for y in (0..im.h).rev() {
+            for x in 0..im.w {
+                let c = im.get(x, y);
+                buf.push(math::Vec3f {
+                    x: c.r,
+                    y: c.g,
+                    z: c.b,
+                });
+            }
+        }
+        Level {
+            w: im.w,
+            h: im.h,
+            buf: buf,
+        }
+    }
+
+    // Color of texel x,y, 0,0 is the top left.
+    fn texel(&self, x: usize, y: usize) -> math::Vec3f {
+        self.buf[x + y * self.w]
+    }
+
+    // Color of the texel under uv, wrapping around horizontally and clamping at the poles.
+    fn sample(&self, uv: math::Vec3f) -> math::Vec3f {
+        let (w, h) = (self.w as i64, self.h as i64);
+        // Casts saturate, and NaN casts to 0.
+        let x = (uv.x * w as f32).floor() as i64;
+        let y = ((1. - uv.y) * h as f32).floor() as i64;
+        self.texel(((x % w + w) % w) as usize, y.max(0).min(h - 1) as usize)
+    }
+}
+
+fn linear_to_rgb(c: math::Vec3f) -> draw::RGB {
+    draw::RGB {
+        r: color::linear_to_srgb(c.x),
+        g: color::linear_to_srgb(c.y),
+        b: color::linear_to_srgb(c.z),
+    }
+}
+
+// Real spherical harmonics basis functions of the first three bands at unit vector d.
+fn sh_basis(d: math::Vec3f) -> [f32; 9] {
+    [0.282095,
+     0.488603 * d.y,
+     0.488603 * d.z,
+     0.488603 * d.x,
+     1.092548 * d.x * d.y,
+     1.092548 * d.y * d.z,
+     0.315392 * (3. * d.z * d.z - 1.),
+     1.092548 * d.x * d.z,
+     0.546274 * (d.x * d.x - d.y * d.y)]
+}
+
+// downsample returns t at half size, averaging 2x2 texels and then blurring 3x3.  The blur wraps
+// around horizontally and clamps at the poles.
+fn downsample(t: &Level) -> Level {
+    let (w, h) = (t.w / 2, t.h / 2);
+    let mut half = vec![math::Vec3f::zero(); w * h];
+    for y in 0..h {
+        for x in 0..w {
+            let sum = t.texel(2 * x, 2 * y) + t.texel(2 * x + 1, 2 * y) +
+                      t.texel(2 * x, 2 * y + 1) + t.texel(2 * x + 1, 2 * y + 1);
+            half[x + y * w] = sum.scale(0.25);
+        }
+    }
+    let mut buf = vec![math::Vec3f::zero(); w * h];
+    for y in 0..h as i64 {
+        for x in 0..w as i64 {
+            let mut sum = math::Vec3f::zero();
+            for by in y - 1..y + 2 {
+                for bx in x - 1..x + 2 {
+                    let sx = ((bx + w as i64) % w as i64) as usize;
+                    let sy = by.max(0).min(h as i64 - 1) as usize;
+                    sum = sum + half[sx + sy * w];
+                }
+            }
+            buf[x as usize + y as usize * w] = sum.scale(1. / 9.);
+        }
+    }
+    Level {
+        w: w,
+        h: h,
+        buf: buf,
+    }
+}
+
+// cube_to_equirect resamples six square cube map faces, ordered +x, -x, +y, -y, +z, -z as in
+// OpenGL, into an equirectangular texture.
+fn cube_to_equirect(faces: &[draw::Texture2D]) -> draw::Texture2D {
+    let size = faces[0].w;
+    let (w, h) = (size * 4, size * 2);
+    let mut buf = vec![0; w * h * 4];
+    for y in 0..h {
+        for x in 0..w {
+            let d = uv_to_direction((x as f32 + 0.5) / w as f32,
+                                    1. - (y as f32 + 0.5) / h as f32);
+            let (ax, ay, az) = (d.x.abs(), d.y.abs(), d.z.abs());
+            // Face, and coordinates on it from the top left, of the major axis.
+            let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+                if d.x > 0. {
+                    (0, -d.z, -d.y, ax)
+                } else {
+                    (1, d.z, -d.y, ax)
+                }
+            } else if ay >= az {
+                if d.y > 0. {
+                    (2, d.x, d.z, ay)
+                } else {
+                    (3, d.x, -d.z, ay)
+                }
+            } else if d.z > 0. {
+                (4, d.x, -d.y, az)
+            } else {
+                (5, -d.x, -d.y, az)
+            };
+            let f = &faces[face];
+            let fx = (((sc / ma + 1.) / 2. * f.w as f32) as usize).min(f.w - 1);
+            let fy = (((tc / ma + 1.) / 2. * f.h as f32) as usize).min(f.h - 1);
+            let src = (fx + fy * f.w) * 4;
+            let dst = (x + y * w) * 4;
+            buf[dst..dst + 4].copy_from_slice(&f.buf[src..src + 4]);
+        }
+    }
+    draw::Texture2D::new(w, h, buf)
+}
+
+impl EnvironmentMap {
+    // read loads an equirectangular environment map.
+    pub fn read<P: AsRef<path::Path>>(filepath: P) -> imagefmt::Result<Self> {
+        let tex = try!(draw::Texture2D::read(filepath));
+        Ok(EnvironmentMap::from_texture(tex))
+    }
+
+    // read_hdr loads an equirectangular environment map with hdr::HdrImage::load, keeping light
+    // above 1 from the float formats.
+    pub fn read_hdr<P: AsRef<path::Path>>(filepath: P) -> imageio::Result<Self> {
+        let im = try!(hdr::HdrImage::load(filepath));
+        Ok(EnvironmentMap::from_hdr(&im))
+    }
+
+    // read_cube loads a cube map from six square images, ordered +x, -x, +y, -y, +z, -z.
+    pub fn read_cube<P: AsRef<path::Path>>(filepaths: &[P; 6]) -> imagefmt::Result<Self> {
+        let mut faces = Vec::with_capacity(6);
+        for p in filepaths.iter() {
+            faces.push(try!(draw::Texture2D::read(p)));
+        }
+        Ok(EnvironmentMap::from_texture(cube_to_equirect(&faces)))
+    }
+
+    // from_texture builds the prefiltered levels and irradiance of the equirectangular sRGB map
+    // tex.
+    pub fn from_texture(tex: draw::Texture2D) -> Self {
+        EnvironmentMap::from_level(Level::from_texture(&tex))
+    }
+
+    // from_hdr builds the prefiltered levels and irradiance of the equirectangular linear map im.
+    pub fn from_hdr(im: &hdr::HdrImage) -> Self {
+        EnvironmentMap::from_level(Level::from_hdr(im))
+    }
+
+    fn from_level(level: Level) -> Self {
+        let mut levels = vec![level];
+        loop {
+            let next = {
+                let last = levels.last().unwrap();
+                if last.w / 2 < MIN_LEVEL_WIDTH || last.h / 2 == 0 {
+                    break;
+                }
+                downsample(last)
+            };
+            levels.push(next);
+        }
+
+        let sh = {
+            let t = levels.iter().find(|t| t.w <= SH_WIDTH).unwrap_or(levels.last().unwrap());
+            let mut sh = [math::Vec3f::zero(); 9];
+            // Solid angle of a texel at the equator.
+            let d_omega = (2. * PI / t.w as f32) * (PI / t.h as f32);
+            for y in 0..t.h {
+                for x in 0..t.w {
+                    let d = uv_to_direction((x as f32 + 0.5) / t.w as f32,
+                                            1. - (y as f32 + 0.5) / t.h as f32);
+                    // Texels shrink towards the poles.
+                    let weight = d_omega * (1. - d.y * d.y).sqrt();
+                    let c = t.texel(x, y).scale(weight);
+                    for (i, b) in sh_basis(d).iter().enumerate() {
+                        sh[i] = sh[i] + c.scale(*b);
+                    }
+                }
+            }
+            sh
+        };
+        info!("Environment map {}x{} with {} levels",
+              levels[0].w,
+              levels[0].h,
+              levels.len());
+        EnvironmentMap {
+            levels: levels,
+            sh: sh,
+        }
+    }
+
+    // sample returns the light arriving from direction dir, unfiltered.
+    pub fn sample(&self, dir: math::Vec3f) -> math::Vec3f {
+        self.sample_level(0, dir)
+    }
+
+    fn sample_level(&self, level: usize, dir: math::Vec3f) -> math::Vec3f {
+        self.levels[level].sample(direction_to_uv(dir))
+    }
+
+    // sample_glossy returns the light arriving from around direction dir, blurred more with
+    // roughness from 0, a mirror, to 1, the blurriest level.
+    pub fn sample_glossy(&self, dir: math::Vec3f, roughness: f32) -> math::Vec3f {
+        let lod = roughness.max(0.).min(1.) * (self.levels.len() - 1) as f32;
+        let i = (lod.floor() as usize).min(self.levels.len() - 1);
+        if i + 1 == self.levels.len() {
+            return self.sample_level(i, dir);
+        }
+        let f = lod - i as f32;
+        self.sample_level(i, dir).scale(1. - f) + self.sample_level(i + 1, dir).scale(f)
+    }
+
+    // irradiance returns the light a white Lambertian surface with normal n reflects, the cosine
+    // weighted average of the map over the hemisphere around n.
+    pub fn irradiance(&self, n: math::Vec3f) -> math::Vec3f {
+        // Convolution with the clamped cosine per band, divided by pi for the Lambertian BRDF.
+        let band = [1., 2. / 3., 2. / 3., 2. / 3., 0.25, 0.25, 0.25, 0.25, 0.25];
+        let mut e = math::Vec3f::zero();
+        for (i, b) in sh_basis(n.normalize()).iter().enumerate() {
+            e = e + self.sh[i].scale(band[i] * b);
+        }
+        math::Vec3f {
+            x: e.x.max(0.),
+            y: e.y.max(0.),
+            z: e.z.max(0.),
+        }
+    }
+
+    // draw_background fills the pixels of im where nothing was drawn in z_buffer with the map as
+    // seen from world's camera.
+    pub fn draw_background(&self,
+                           world: &World,
+                           im: &mut draw::Image,
+                           z_buffer: &draw::DepthBuffer) {
+        let inv = world.vp_p_mv.inverse().unwrap();
+        for y in 0..im.h {
+            for x in 0..im.w {
+                if z_buffer.get(x, y) != f32::MIN {
+                    continue;
+                }
+                let p = inv.transform(math::Vec3f {
+                    x: x as f32 + 0.5,
+                    y: y as f32 + 0.5,
+                    z: shader::DEPTH_RESOLUTION / 2.,
+                });
+                im.set(x, y, linear_to_rgb(self.sample(p - world.eye())));
+            }
+        }
+    }
+}
+
+// reflect returns the direction i, pointing at the surface, bounces to off a surface with unit
+// normal n.
+pub fn reflect(i: math::Vec3f, n: math::Vec3f) -> math::Vec3f {
+    i - n.scale(2. * math::dot(i, n))
+}
+
+// ReflectionShader renders a perfectly reflective surface showing an environment map, a mirror
+// at roughness 0 and increasingly glossy up to 1.
+#[derive(Clone)]
+pub struct ReflectionShader<'a> {
+    // Uniform values.
+    env: &'a EnvironmentMap,
+    roughness: f32,
+
+    // Varying values, written by vertex shader, read by fragment shader
+    // Vertex normal at fragment.
+    ns: [math::Vec3f; 3],
+    // Model space position at fragment.
+    verts: [math::Vec3f; 3],
+}
+
+impl<'a> ReflectionShader<'a> {
+    pub fn new(env: &'a EnvironmentMap) -> Self {
+        ReflectionShader {
+            env: env,
+            roughness: 0.,
+            ns: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+            verts: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+        }
+    }
+
+    pub fn set_roughness(&mut self, roughness: f32) {
+        self.roughness = roughness;
+    }
+}
+
+impl<'a> Shader for ReflectionShader<'a> {
+    fn vertex(&mut self, world: &World, f: &wavefront::Face) -> [math::Vec3f; 3] {
+        // screen space vertices of the face.
+        let mut screen_verts = [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()];
+        for i in 0..3 {
+            self.ns[i] = f.normals[i];
+            self.verts[i] = f.vertices[i];
+            screen_verts[i] = world.vp_p_mv.transform(f.vertices[i]);
+        }
+        screen_verts
+    }
+
+    fn fragment(&self, world: &World, bc: math::Vec3f) -> Option<draw::RGB> {
+        self.fragment_linear(world, bc).map(|c| c.to_srgb())
+    }
+
+    fn fragment_linear(&self, world: &World, bc: math::Vec3f) -> Option<hdr::Rgba> {
+        let n = (self.ns[0].scale(bc.x) + self.ns[1].scale(bc.y) + self.ns[2].scale(bc.z))
+            .normalize();
+        let p = self.verts[0].scale(bc.x) + self.verts[1].scale(bc.y) + self.verts[2].scale(bc.z);
+        let r = reflect((p - world.eye()).normalize(), n);
+        let c = self.env.sample_glossy(r, self.roughness);
+        Some(hdr::Rgba {
+            r: c.x,
+            g: c.y,
+            b: c.z,
+            a: 1.,
+        })
+    }
+}
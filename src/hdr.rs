@@ -0,0 +1,176 @@
+use std::path;
+
+use color;
+use draw;
+use imageio;
+
+// Linear light color with alpha.  Channels aren't limited to [0, 1], bright lights and specular
+// highlights go well above 1 until tone mapped.
+#[derive(Copy, Clone, Debug)]
+pub struct Rgba {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Rgba {
+    // Decodes the sRGB color c to linear, opaque.
+    pub fn from_srgb(c: draw::RGB) -> Self {
+        Rgba {
+            r: color::srgb_to_linear(c.r),
+            g: color::srgb_to_linear(c.g),
+            b: color::srgb_to_linear(c.b),
+            a: 1.,
+        }
+    }
+
+    // Encodes the color as sRGB, clamped to [0, 1].  Alpha is dropped.
+    pub fn to_srgb(&self) -> draw::RGB {
+        draw::RGB {
+            r: color::linear_to_srgb(self.r),
+            g: color::linear_to_srgb(self.g),
+            b: color::linear_to_srgb(self.b),
+        }
+    }
+}
+
+// How HdrImage::resolve compresses unbounded linear light into the displayable [0, 1].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToneMap {
+    // Clip every channel at 1, what writing straight to an Image does.
+    Clamp,
+    // c / (1 + c) per channel, never quite reaches white.
+    Reinhard,
+    // Narkowicz's fit of the ACES filmic curve, with a toe darkening shadows and a shoulder rolling
+    // highlights off to white.
+    AcesFilmic,
+}
+
+impl ToneMap {
+    fn apply(&self, c: f32) -> f32 {
+        let c = c.max(0.);
+        match *self {
+            ToneMap::Clamp => c.min(1.),
+            ToneMap::Reinhard => c / (1. + c),
+            ToneMap::AcesFilmic => {
+                let (a, b, c2, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                ((c * (a * c + b)) / (c * (c2 * c + d) + e)).min(1.)
+            }
+        }
+    }
+}
+
+// HdrImage is a framebuffer of linear, floating point RGBA pixels.  Shading into it with
+// render::Renderer keeps light above 1 and the precision of dark values, and resolve turns it into
+// an 8-bit sRGB Image once at the end.
+#[derive(Debug)]
+pub struct HdrImage {
+    pub w: usize,
+    pub h: usize,
+    pub buf: Vec<f32>,
+}
+
+impl HdrImage {
+    pub fn new(w: usize, h: usize) -> Self {
+        HdrImage {
+            w: w,
+            h: h,
+            buf: vec![0.; w * h * 4],
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, c: Rgba) {
+        if x >= self.w || y >= self.h {
+            error!("Out of bounds set pixel {},{} size {}x{}",
+                   x,
+                   y,
+                   self.w,
+                   self.h);
+            return;
+        }
+        let off = (x + y * self.w) * 4;
+        self.buf[off + 0] = c.r;
+        self.buf[off + 1] = c.g;
+        self.buf[off + 2] = c.b;
+        self.buf[off + 3] = c.a;
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Rgba {
+        if x >= self.w || y >= self.h {
+            error!("Out of bounds get pixel {},{} size {}x{}",
+                   x,
+                   y,
+                   self.w,
+                   self.h);
+            return Rgba {
+                r: 0.,
+                g: 0.,
+                b: 0.,
+                a: 0.,
+            };
+        }
+        let off = (x + y * self.w) * 4;
+        Rgba {
+            r: self.buf[off + 0],
+            g: self.buf[off + 1],
+            b: self.buf[off + 2],
+            a: self.buf[off + 3],
+        }
+    }
+
+    // sub_image returns a copy of the w x h region of the image with its corner at x,y.
+    pub fn sub_image(&self, x: usize, y: usize, w: usize, h: usize) -> HdrImage {
+        let mut sub = HdrImage::new(w, h);
+        for row in 0..h {
+            let src = (x + (y + row) * self.w) * 4;
+            sub.buf[row * w * 4..(row + 1) * w * 4].copy_from_slice(&self.buf[src..src + w * 4]);
+        }
+        sub
+    }
+
+    // blit copies all of src into the image with src's corner at x,y.
+    pub fn blit(&mut self, src: &HdrImage, x: usize, y: usize) {
+        let stride = src.w * 4;
+        for row in 0..src.h {
+            let dst = (x + (y + row) * self.w) * 4;
+            self.buf[dst..dst + stride].copy_from_slice(&src.buf[row * stride..(row + 1) * stride]);
+        }
+    }
+
+    // save writes the image to path, in a float format named by its extension.  resolve it first
+    // for the 8-bit formats.  See imageio::Format for the formats supported.
+    pub fn save<P: AsRef<path::Path>>(&self, path: P) -> imageio::Result<()> {
+        imageio::save_rgba(path, self.w, self.h, &self.buf)
+    }
+
+    // load reads the image at path, in the format named by its extension.  8-bit images are
+    // decoded to linear.
+    pub fn load<P: AsRef<path::Path>>(path: P) -> imageio::Result<HdrImage> {
+        let (w, h, buf) = try!(imageio::load_rgba(path));
+        Ok(HdrImage {
+            w: w,
+            h: h,
+            buf: buf,
+        })
+    }
+
+    // resolve scales every pixel by exposure, tone maps it and encodes it as sRGB.  Alpha is
+    // dropped.
+    pub fn resolve(&self, tone_map: ToneMap, exposure: f32) -> draw::Image {
+        let mut im = draw::Image::new(self.w, self.h);
+        for y in 0..self.h {
+            for x in 0..self.w {
+                let c = self.get(x, y);
+                im.set(x,
+                       y,
+                       draw::RGB {
+                           r: color::linear_to_srgb(tone_map.apply(c.r * exposure)),
+                           g: color::linear_to_srgb(tone_map.apply(c.g * exposure)),
+                           b: color::linear_to_srgb(tone_map.apply(c.b * exposure)),
+                       });
+            }
+        }
+        im
+    }
+}
@@ -0,0 +1,570 @@
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use imagefmt;
+use imagefmt::{ColFmt, ColType};
+
+use color;
+
+// File formats draw::Image::save and hdr::HdrImage::save write, chosen by the file's extension.
+// Images are stored with their bottom row first, screen space being y up, and each format's own
+// origin is handled when reading and writing so callers never flip.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    // 8-bit sRGB formats, written with imagefmt.
+    Png,
+    Tga,
+    Bmp,
+    // Binary 8-bit sRGB portable pixmap.  ASCII pixmaps can be read too.
+    Ppm,
+    // Portable float map, linear RGB.  Alpha is dropped.
+    Pfm,
+    // Uncompressed OpenEXR, linear 32-bit float RGBA.  Only uncompressed scanline files can be
+    // read.
+    Exr,
+}
+
+impl Format {
+    // from_path returns the format named by path's extension, ignoring case.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Format> {
+        let ext = match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some(ext) => ext.to_lowercase(),
+            None => return None,
+        };
+        match ext.as_str() {
+            "png" => Some(Format::Png),
+            "tga" => Some(Format::Tga),
+            "bmp" => Some(Format::Bmp),
+            "ppm" => Some(Format::Ppm),
+            "pfm" => Some(Format::Pfm),
+            "exr" => Some(Format::Exr),
+            _ => None,
+        }
+    }
+
+    // is_float returns true for formats storing linear floating point color.
+    pub fn is_float(&self) -> bool {
+        match *self {
+            Format::Pfm | Format::Exr => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum ErrorRepr {
+    FormatError,
+    IoError(io::Error),
+    ImagefmtError(imagefmt::Error),
+}
+
+#[derive(Debug)]
+pub struct ImageError {
+    desc: &'static str,
+    cause: ErrorRepr,
+}
+
+impl ImageError {
+    fn format(desc: &'static str) -> ImageError {
+        ImageError {
+            desc: desc,
+            cause: ErrorRepr::FormatError,
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(err: io::Error) -> ImageError {
+        ImageError {
+            desc: "IO error",
+            cause: ErrorRepr::IoError(err),
+        }
+    }
+}
+
+impl From<imagefmt::Error> for ImageError {
+    fn from(err: imagefmt::Error) -> ImageError {
+        ImageError {
+            desc: "image codec error",
+            cause: ErrorRepr::ImagefmtError(err),
+        }
+    }
+}
+
+impl error::Error for ImageError {
+    fn description(&self) -> &str {
+        self.desc
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match self.cause {
+            ErrorRepr::FormatError => None,
+            ErrorRepr::IoError(ref err) => Some(err as &error::Error),
+            ErrorRepr::ImagefmtError(ref err) => Some(err as &error::Error),
+        }
+    }
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.desc.fmt(f)
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, ImageError>;
+
+fn format_of<P: AsRef<Path>>(path: P) -> Result<Format> {
+    Format::from_path(path).ok_or(ImageError::format("unknown image file extension"))
+}
+
+// flip_rows returns buf, h rows of stride values, with the order of its rows reversed.
+fn flip_rows<T: Copy>(buf: &[T], stride: usize, h: usize) -> Vec<T> {
+    let mut out = Vec::with_capacity(buf.len());
+    for y in (0..h).rev() {
+        out.extend_from_slice(&buf[y * stride..(y + 1) * stride]);
+    }
+    out
+}
+
+// save_rgb writes the w x h 8-bit sRGB image buf, 3 bytes per pixel, to path.  Float formats
+// store it decoded to linear.
+pub fn save_rgb<P: AsRef<Path>>(path: P, w: usize, h: usize, buf: &[u8]) -> Result<()> {
+    let format = try!(format_of(&path));
+    if format.is_float() {
+        return save_rgba(path, w, h, &rgb_to_linear(buf, 3));
+    }
+    info!("Writing {:?} image {:?}", format, path.as_ref());
+    let top_down = flip_rows(buf, w * 3, h);
+    match format {
+        Format::Ppm => {
+            let mut out = format!("P6\n{} {}\n255\n", w, h).into_bytes();
+            out.extend_from_slice(&top_down);
+            try!(try!(fs::File::create(path)).write_all(&out));
+        }
+        _ => try!(imagefmt::write(path, w, h, ColFmt::RGB, &top_down, ColType::Color)),
+    }
+    Ok(())
+}
+
+// load_rgb reads the image at path as 8-bit sRGB, 3 bytes per pixel, returning its width, height
+// and pixels.  Float formats are clamped and encoded.
+pub fn load_rgb<P: AsRef<Path>>(path: P) -> Result<(usize, usize, Vec<u8>)> {
+    let format = try!(format_of(&path));
+    if format.is_float() {
+        let (w, h, buf) = try!(load_rgba(path));
+        let rgb = buf.chunks(4)
+            .flat_map(|c| {
+                vec![color::linear_to_srgb(c[0]),
+                     color::linear_to_srgb(c[1]),
+                     color::linear_to_srgb(c[2])]
+            })
+            .collect();
+        return Ok((w, h, rgb));
+    }
+    info!("Reading {:?} image {:?}", format, path.as_ref());
+    let (w, h, top_down) = match format {
+        Format::Ppm => try!(read_ppm(&try!(read_file(path)))),
+        _ => {
+            let im = try!(imagefmt::read(path, ColFmt::RGB));
+            (im.w, im.h, im.buf)
+        }
+    };
+    Ok((w, h, flip_rows(&top_down, w * 3, h)))
+}
+
+// save_rgba writes the w x h linear float image buf, RGBA per pixel, to path.  Only float formats
+// are supported, resolve to a draw::Image to choose how to tone map for the others.
+pub fn save_rgba<P: AsRef<Path>>(path: P, w: usize, h: usize, buf: &[f32]) -> Result<()> {
+    let format = try!(format_of(&path));
+    info!("Writing {:?} image {:?}", format, path.as_ref());
+    let out = match format {
+        Format::Pfm => write_pfm(w, h, buf),
+        Format::Exr => write_exr(w, h, buf),
+        _ => return Err(ImageError::format("8-bit format needs a tone mapped image")),
+    };
+    try!(try!(fs::File::create(path)).write_all(&out));
+    Ok(())
+}
+
+// load_rgba reads the image at path as linear float RGBA, returning its width, height and pixels.
+// 8-bit formats are decoded from sRGB.
+pub fn load_rgba<P: AsRef<Path>>(path: P) -> Result<(usize, usize, Vec<f32>)> {
+    let format = try!(format_of(&path));
+    info!("Reading {:?} image {:?}", format, path.as_ref());
+    match format {
+        Format::Pfm => read_pfm(&try!(read_file(path))),
+        Format::Exr => read_exr(&try!(read_file(path))),
+        Format::Ppm => {
+            let (w, h, rgb) = try!(load_rgb(path));
+            Ok((w, h, rgb_to_linear(&rgb, 3)))
+        }
+        _ => {
+            let im = try!(imagefmt::read(path, ColFmt::RGBA));
+            let (w, h) = (im.w, im.h);
+            Ok((w, h, rgb_to_linear(&flip_rows(&im.buf, w * 4, h), 4)))
+        }
+    }
+}
+
+// rgb_to_linear decodes 8-bit sRGB pixels of n channels, alpha fourth if present, to linear RGBA.
+fn rgb_to_linear(buf: &[u8], n: usize) -> Vec<f32> {
+    buf.chunks(n)
+        .flat_map(|c| {
+            let a = if n == 4 { c[3] as f32 / 255. } else { 1. };
+            vec![color::srgb_to_linear(c[0]),
+                 color::srgb_to_linear(c[1]),
+                 color::srgb_to_linear(c[2]),
+                 a]
+        })
+        .collect()
+}
+
+fn read_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    try!(try!(fs::File::open(path)).read_to_end(&mut data));
+    Ok(data)
+}
+
+// header returns the first n whitespace separated tokens of a PPM or PFM file, skipping comments,
+// and the offset of the data following the single whitespace character after the last.
+fn header(data: &[u8], n: usize) -> Result<(Vec<String>, usize)> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while tokens.len() < n {
+        while i < data.len() && (data[i] as char).is_whitespace() {
+            i += 1;
+        }
+        if i < data.len() && data[i] == b'#' {
+            while i < data.len() && data[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+        let start = i;
+        while i < data.len() && !(data[i] as char).is_whitespace() {
+            i += 1;
+        }
+        if start == i {
+            return Err(ImageError::format("truncated header"));
+        }
+        tokens.push(String::from_utf8_lossy(&data[start..i]).into_owned());
+    }
+    Ok((tokens, i + 1))
+}
+
+fn parse<T: ::std::str::FromStr>(token: &str) -> Result<T> {
+    token.parse().map_err(|_| ImageError::format("malformed header"))
+}
+
+// pixels returns the number of pixels in a w x h image, checking the file's remaining available
+// bytes can hold them at bytes_per_pixel each.  Decoders call it before allocating so a malformed
+// header can't ask for more memory than the file could fill.
+fn pixels(w: usize, h: usize, bytes_per_pixel: usize, available: usize) -> Result<usize> {
+    match w.checked_mul(h) {
+        Some(n) if n.checked_mul(bytes_per_pixel).map_or(false, |b| b <= available) => Ok(n),
+        _ => Err(ImageError::format("image size larger than its data")),
+    }
+}
+
+// read_ppm decodes a binary or ASCII PPM to 8-bit RGB, top row first.
+fn read_ppm(data: &[u8]) -> Result<(usize, usize, Vec<u8>)> {
+    let (tokens, start) = try!(header(data, 4));
+    let (w, h, max): (usize, usize, u32) =
+        (try!(parse(&tokens[1])), try!(parse(&tokens[2])), try!(parse(&tokens[3])));
+    if max == 0 || max > 65535 {
+        return Err(ImageError::format("PPM maximum value out of range"));
+    }
+    // Every value takes at least one byte, two for 16-bit binary files.
+    let bytes = if tokens[0] == "P6" && max > 255 { 6 } else { 3 };
+    let n = try!(pixels(w, h, bytes, data.len().saturating_sub(start))) * 3;
+    let values: Vec<u32> = match tokens[0].as_str() {
+        "P6" if max < 256 => data[start.min(data.len())..].iter().map(|&v| v as u32).collect(),
+        "P6" => {
+            data[start.min(data.len())..]
+                .chunks(2)
+                .filter(|c| c.len() == 2)
+                .map(|c| (c[0] as u32) << 8 | c[1] as u32)
+                .collect()
+        }
+        "P3" => {
+            let text = String::from_utf8_lossy(&data[start.min(data.len())..]).into_owned();
+            try!(text.split_whitespace().take(n).map(|t| parse(t)).collect::<Result<_>>())
+        }
+        _ => return Err(ImageError::format("unsupported PPM type")),
+    };
+    if values.len() < n {
+        return Err(ImageError::format("truncated PPM data"));
+    }
+    let buf = values[..n].iter().map(|&v| ((v.min(max) * 255 + max / 2) / max) as u8).collect();
+    Ok((w, h, buf))
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]);
+}
+
+fn put_f32(out: &mut Vec<u8>, v: f32) {
+    put_u32(out, v.to_bits());
+}
+
+fn get_u32(data: &[u8], off: usize) -> Result<u32> {
+    if off + 4 > data.len() {
+        return Err(ImageError::format("truncated data"));
+    }
+    Ok(data[off] as u32 | (data[off + 1] as u32) << 8 | (data[off + 2] as u32) << 16 |
+       (data[off + 3] as u32) << 24)
+}
+
+// write_pfm encodes RGBA pixels, bottom row first as PFM stores them, dropping alpha.
+fn write_pfm(w: usize, h: usize, buf: &[f32]) -> Vec<u8> {
+    // A negative scale marks the data little endian.
+    let mut out = format!("PF\n{} {}\n-1.0\n", w, h).into_bytes();
+    for c in buf.chunks(4) {
+        for &v in &c[..3] {
+            put_f32(&mut out, v);
+        }
+    }
+    out
+}
+
+fn read_pfm(data: &[u8]) -> Result<(usize, usize, Vec<f32>)> {
+    let (tokens, start) = try!(header(data, 4));
+    let channels = match tokens[0].as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(ImageError::format("unsupported PFM type")),
+    };
+    let (w, h, scale): (usize, usize, f32) =
+        (try!(parse(&tokens[1])), try!(parse(&tokens[2])), try!(parse(&tokens[3])));
+    let n = try!(pixels(w, h, channels * 4, data.len().saturating_sub(start)));
+    let mut buf = Vec::with_capacity(n * 4);
+    for i in 0..n {
+        let mut c = [0.; 3];
+        for ch in 0..channels {
+            let bits = try!(get_u32(data, start + (i * channels + ch) * 4));
+            let bits = if scale < 0. { bits } else { bits.swap_bytes() };
+            c[ch] = f32::from_bits(bits);
+        }
+        if channels == 1 {
+            c = [c[0]; 3];
+        }
+        buf.extend_from_slice(&[c[0], c[1], c[2], 1.]);
+    }
+    Ok((w, h, buf))
+}
+
+// OpenEXR pixel types.
+const EXR_UINT: u32 = 0;
+const EXR_HALF: u32 = 1;
+const EXR_FLOAT: u32 = 2;
+
+fn put_attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    out.extend_from_slice(name.as_bytes());
+    out.push(0);
+    out.extend_from_slice(kind.as_bytes());
+    out.push(0);
+    put_u32(out, value.len() as u32);
+    out.extend_from_slice(value);
+}
+
+// write_exr encodes RGBA pixels as a single part, uncompressed, scanline OpenEXR file with FLOAT
+// channels, flipping to its top row first order.
+fn write_exr(w: usize, h: usize, buf: &[f32]) -> Vec<u8> {
+    let mut out = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+    // Channels are stored in alphabetical order.
+    let channels = [("A", 3), ("B", 2), ("G", 1), ("R", 0)];
+    let mut chlist = Vec::new();
+    for &(name, _) in &channels {
+        chlist.extend_from_slice(name.as_bytes());
+        chlist.push(0);
+        put_u32(&mut chlist, EXR_FLOAT);
+        // pLinear and reserved bytes, then x and y sampling.
+        put_u32(&mut chlist, 0);
+        put_u32(&mut chlist, 1);
+        put_u32(&mut chlist, 1);
+    }
+    chlist.push(0);
+    let mut window = Vec::new();
+    for &v in &[0, 0, w as i32 - 1, h as i32 - 1] {
+        put_u32(&mut window, v as u32);
+    }
+    let mut one = Vec::new();
+    put_f32(&mut one, 1.);
+    put_attribute(&mut out, "channels", "chlist", &chlist);
+    put_attribute(&mut out, "compression", "compression", &[0]);
+    put_attribute(&mut out, "dataWindow", "box2i", &window);
+    put_attribute(&mut out, "displayWindow", "box2i", &window);
+    put_attribute(&mut out, "lineOrder", "lineOrder", &[0]);
+    put_attribute(&mut out, "pixelAspectRatio", "float", &one);
+    put_attribute(&mut out, "screenWindowCenter", "v2f", &[0; 8]);
+    put_attribute(&mut out, "screenWindowWidth", "float", &one);
+    out.push(0);
+
+    // Offsets of each scanline block, a y coordinate and byte count followed by the row of each
+    // channel in turn.
+    let block = 8 + w * 4 * channels.len();
+    let first = out.len() + h * 8;
+    for y in 0..h {
+        let off = (first + y * block) as u64;
+        put_u32(&mut out, off as u32);
+        put_u32(&mut out, (off >> 32) as u32);
+    }
+    for y in 0..h {
+        put_u32(&mut out, y as u32);
+        put_u32(&mut out, (w * 4 * channels.len()) as u32);
+        let row = h - 1 - y;
+        for &(_, c) in &channels {
+            for x in 0..w {
+                put_f32(&mut out, buf[(x + row * w) * 4 + c]);
+            }
+        }
+    }
+    out
+}
+
+// half_to_f32 decodes an IEEE 754 half precision float.
+fn half_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1. } else { 1. };
+    let exp = (h >> 10 & 0x1f) as i32;
+    let frac = (h & 0x3ff) as f32;
+    sign *
+    match exp {
+        0 => frac * 2f32.powi(-24),
+        31 => if frac == 0. { ::std::f32::INFINITY } else { ::std::f32::NAN },
+        _ => (1. + frac / 1024.) * 2f32.powi(exp - 15),
+    }
+}
+
+// read_cstr returns the NUL terminated string at off in data and the offset following it.
+fn read_cstr(data: &[u8], off: usize) -> Result<(String, usize)> {
+    match data[off.min(data.len())..].iter().position(|&b| b == 0) {
+        Some(n) => Ok((String::from_utf8_lossy(&data[off..off + n]).into_owned(), off + n + 1)),
+        None => Err(ImageError::format("truncated EXR header")),
+    }
+}
+
+// read_exr decodes a single part, uncompressed, scanline OpenEXR file with R, G and B, and
+// optionally A, channels.  Missing alpha is opaque.
+fn read_exr(data: &[u8]) -> Result<(usize, usize, Vec<f32>)> {
+    if data.len() < 8 || data[..4] != [0x76, 0x2f, 0x31, 0x01] {
+        return Err(ImageError::format("not an OpenEXR file"));
+    }
+    // Tiled, deep and multipart files aren't supported.
+    if try!(get_u32(data, 4)) & 0x1a00 != 0 {
+        return Err(ImageError::format("only scanline OpenEXR files are supported"));
+    }
+    let mut off = 8;
+    // Name and pixel type of each channel, in file order.
+    let mut channels: Vec<(String, u32)> = Vec::new();
+    let mut window = None;
+    loop {
+        let (name, next) = try!(read_cstr(data, off));
+        off = next;
+        if name.is_empty() {
+            break;
+        }
+        let (_, next) = try!(read_cstr(data, off));
+        let size = try!(get_u32(data, next)) as usize;
+        let value = next + 4;
+        off = value + size;
+        if off > data.len() {
+            return Err(ImageError::format("truncated EXR header"));
+        }
+        match name.as_str() {
+            "channels" => {
+                let mut c = value;
+                while c < off && data[c] != 0 {
+                    let (ch, next) = try!(read_cstr(data, c));
+                    channels.push((ch, try!(get_u32(data, next))));
+                    c = next + 16;
+                }
+            }
+            "compression" if size == 0 => {
+                return Err(ImageError::format("truncated EXR header"));
+            }
+            "compression" if data[value] != 0 => {
+                return Err(ImageError::format("only uncompressed OpenEXR files are supported"));
+            }
+            "dataWindow" => {
+                let mut v = [0i32; 4];
+                for i in 0..4 {
+                    v[i] = try!(get_u32(data, value + i * 4)) as i32;
+                }
+                window = Some(v);
+            }
+            _ => {}
+        }
+    }
+    let window = try!(window.ok_or(ImageError::format("EXR missing dataWindow")));
+    // Widened so corners far apart can't overflow.
+    let (w, h) = (window[2] as i64 - window[0] as i64 + 1, window[3] as i64 - window[1] as i64 + 1);
+    if w <= 0 || h <= 0 {
+        return Err(ImageError::format("empty EXR dataWindow"));
+    }
+    let (w, h) = (w as usize, h as usize);
+    let mut bytes_per_pixel = 0;
+    for &(_, kind) in &channels {
+        bytes_per_pixel += match kind {
+            EXR_HALF => 2,
+            EXR_FLOAT | EXR_UINT => 4,
+            _ => return Err(ImageError::format("unknown EXR pixel type")),
+        };
+    }
+    if bytes_per_pixel == 0 {
+        return Err(ImageError::format("EXR has no channels"));
+    }
+    let n = try!(pixels(w, h, bytes_per_pixel, data.len().saturating_sub(off)));
+    let dest = |name: &str| match name {
+        "R" => Some(0),
+        "G" => Some(1),
+        "B" => Some(2),
+        "A" => Some(3),
+        _ => None,
+    };
+    let mut buf = vec![0.; n * 4];
+    if !channels.iter().any(|c| c.0 == "A") {
+        for p in buf.chunks_mut(4) {
+            p[3] = 1.;
+        }
+    }
+    for i in 0..h {
+        // Offsets are 64 bits, files past 4GB aren't supported.
+        let lo = try!(get_u32(data, off + i * 8)) as usize;
+        let row = try!(get_u32(data, lo)) as i32 as i64 - window[1] as i64;
+        if row < 0 || row >= h as i64 {
+            return Err(ImageError::format("EXR scanline out of range"));
+        }
+        let y = h - 1 - row as usize;
+        let mut p = lo + 8;
+        for &(ref name, kind) in &channels {
+            for x in 0..w {
+                let v = match kind {
+                    EXR_HALF => {
+                        if p + 2 > data.len() {
+                            return Err(ImageError::format("truncated data"));
+                        }
+                        p += 2;
+                        half_to_f32(data[p - 2] as u16 | (data[p - 1] as u16) << 8)
+                    }
+                    EXR_FLOAT => {
+                        p += 4;
+                        f32::from_bits(try!(get_u32(data, p - 4)))
+                    }
+                    EXR_UINT => {
+                        p += 4;
+                        try!(get_u32(data, p - 4)) as f32
+                    }
+                    _ => return Err(ImageError::format("unknown EXR pixel type")),
+                };
+                if let Some(c) = dest(name) {
+                    buf[(x + y * w) * 4 + c] = v;
+                }
+            }
+        }
+    }
+    Ok((w, h, buf))
+}
@@ -3,8 +3,25 @@ extern crate log;
 extern crate imagefmt;
 extern crate math;
 extern crate rand;
+extern crate scoped_threadpool;
 
+pub mod bake;
 pub mod color;
+pub mod debug;
 pub mod draw;
+pub mod env;
+pub mod font;
+pub mod hdr;
+pub mod imageio;
+pub mod light;
+pub mod msaa;
+pub mod oit;
+pub mod post;
+pub mod raster;
+pub mod render;
+pub mod resample;
 pub mod shader;
+pub mod shadow;
+pub mod shape;
+pub mod stroke;
 pub mod wavefront;
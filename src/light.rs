@@ -0,0 +1,149 @@
+use draw;
+use math;
+
+// Falloff of a light with distance d, 1 / (constant + linear * d + quadratic * d^2).
+#[derive(Copy, Clone, Debug)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    pub fn new(constant: f32, linear: f32, quadratic: f32) -> Self {
+        Attenuation {
+            constant: constant,
+            linear: linear,
+            quadratic: quadratic,
+        }
+    }
+
+    fn at(&self, d: f32) -> f32 {
+        1. / (self.constant + self.linear * d + self.quadratic * d * d)
+    }
+}
+
+// Where light comes from. Positions and directions are in model space.
+#[derive(Copy, Clone, Debug)]
+pub enum Source {
+    // Infinitely far away light, dir points towards the light.
+    Directional { dir: math::Vec3f },
+    // Light radiating equally in all directions from pos.
+    Point {
+        pos: math::Vec3f,
+        attenuation: Attenuation,
+    },
+    // Light radiating from pos in a cone around dir.  Within inner radians of dir the light is at
+    // full strength, it fades to nothing at outer radians.
+    Spot {
+        pos: math::Vec3f,
+        dir: math::Vec3f,
+        inner: f32,
+        outer: f32,
+        attenuation: Attenuation,
+    },
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+    pub source: Source,
+    pub color: draw::RGB,
+    pub intensity: f32,
+}
+
+// Light arriving at a point.
+#[derive(Copy, Clone, Debug)]
+pub struct Incident {
+    // Unit vector from the point towards the light, in model space.
+    pub dir: math::Vec3f,
+    // Strength of the light per channel, red, green and blue in x, y and z.  1 is a white light at
+    // intensity 1.
+    pub radiance: math::Vec3f,
+}
+
+fn default_attenuation() -> Attenuation {
+    Attenuation::new(1., 0.1, 0.05)
+}
+
+impl Light {
+    // White directional light shining from dir.
+    pub fn directional(dir: math::Vec3f) -> Self {
+        Light {
+            source: Source::Directional { dir: dir },
+            color: draw::RGB {
+                r: 255,
+                g: 255,
+                b: 255,
+            },
+            intensity: 1.,
+        }
+    }
+
+    // White point light at pos.
+    pub fn point(pos: math::Vec3f) -> Self {
+        Light {
+            source: Source::Point {
+                pos: pos,
+                attenuation: default_attenuation(),
+            },
+            ..Light::directional(pos)
+        }
+    }
+
+    // White spot light at pos pointing along dir, with inner and outer cone half angles in
+    // radians.  The angles are swapped if inner is the larger.
+    pub fn spot(pos: math::Vec3f, dir: math::Vec3f, inner: f32, outer: f32) -> Self {
+        let (inner, outer) = if inner > outer { (outer, inner) } else { (inner, outer) };
+        Light {
+            source: Source::Spot {
+                pos: pos,
+                dir: dir,
+                inner: inner,
+                outer: outer,
+                attenuation: default_attenuation(),
+            },
+            ..Light::directional(dir)
+        }
+    }
+
+    // incident returns the light reaching model space point p, or None if p is outside a spot
+    // light's cone or exactly at a point or spot light, where the light has no direction.
+    pub fn incident(&self, p: math::Vec3f) -> Option<Incident> {
+        let (dir, strength) = match self.source {
+            Source::Directional { dir } => (dir.normalize(), 1.),
+            Source::Point { pos, attenuation } => {
+                let to_light = pos - p;
+                let d = to_light.length();
+                if d == 0. {
+                    return None;
+                }
+                (to_light.scale(1. / d), attenuation.at(d))
+            }
+            Source::Spot { pos, dir, inner, outer, attenuation } => {
+                let to_light = pos - p;
+                let d = to_light.length();
+                if d == 0. {
+                    return None;
+                }
+                let l = to_light.scale(1. / d);
+                // Cosine of the angle between the spot's axis and the direction to p.
+                let cos = -math::dot(l, dir.normalize());
+                let (cos_inner, cos_outer) = (inner.cos(), outer.cos());
+                if cos <= cos_outer {
+                    return None;
+                }
+                let cone = ((cos - cos_outer) / (cos_inner - cos_outer)).max(0.).min(1.);
+                (l, cone * attenuation.at(d))
+            }
+        };
+        let s = strength * self.intensity / 255.;
+        Some(Incident {
+            dir: dir,
+            radiance: math::Vec3f {
+                x: self.color.r as f32 * s,
+                y: self.color.g as f32 * s,
+                z: self.color.b as f32 * s,
+            },
+        })
+    }
+}
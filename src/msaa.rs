@@ -0,0 +1,101 @@
+use std::f32;
+
+use color;
+use draw;
+use raster;
+
+// MsaaBuffer is a multisampled color and depth buffer for render::Renderer::draw_msaa.  Each pixel
+// holds several samples at the positions of raster::sample_pattern, coverage and depth are tested
+// per sample while the shader runs once per pixel, and resolve averages the samples so edges are
+// anti-aliased at a fraction of the cost of supersampling.
+pub struct MsaaBuffer {
+    pub w: usize,
+    pub h: usize,
+    samples: usize,
+    // RGB per sample, samples of a pixel are adjacent.
+    color: Vec<u8>,
+    depth: Vec<f32>,
+}
+
+impl MsaaBuffer {
+    // new returns a black w x h buffer with samples, 2, 4 or 8, per pixel.
+    pub fn new(w: usize, h: usize, samples: usize) -> Self {
+        // Panics on unsupported counts.
+        raster::sample_pattern(samples);
+        MsaaBuffer {
+            w: w,
+            h: h,
+            samples: samples,
+            color: vec![0; w * h * samples * 3],
+            depth: vec![f32::MIN; w * h * samples],
+        }
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    // Sample positions in sixteenths of a pixel from the pixel center.
+    pub fn pattern(&self) -> &'static [(i64, i64)] {
+        raster::sample_pattern(self.samples)
+    }
+
+    fn index(&self, x: usize, y: usize, s: usize) -> usize {
+        (x + y * self.w) * self.samples + s
+    }
+
+    pub fn depth(&self, x: usize, y: usize, s: usize) -> f32 {
+        self.depth[self.index(x, y, s)]
+    }
+
+    pub fn set_depth(&mut self, x: usize, y: usize, s: usize, z: f32) {
+        let i = self.index(x, y, s);
+        self.depth[i] = z;
+    }
+
+    pub fn set_color(&mut self, x: usize, y: usize, s: usize, c: draw::RGB) {
+        let off = self.index(x, y, s) * 3;
+        self.color[off + 0] = c.r;
+        self.color[off + 1] = c.g;
+        self.color[off + 2] = c.b;
+    }
+
+    // resolve averages each pixel's samples, in linear space, into an Image.
+    pub fn resolve(&self) -> draw::Image {
+        let mut im = draw::Image::new(self.w, self.h);
+        for y in 0..self.h {
+            for x in 0..self.w {
+                let mut sum = [0.; 3];
+                for s in 0..self.samples {
+                    let off = self.index(x, y, s) * 3;
+                    for c in 0..3 {
+                        sum[c] += color::srgb_to_linear(self.color[off + c]);
+                    }
+                }
+                let n = self.samples as f32;
+                im.set(x,
+                       y,
+                       draw::RGB {
+                           r: color::linear_to_srgb(sum[0] / n),
+                           g: color::linear_to_srgb(sum[1] / n),
+                           b: color::linear_to_srgb(sum[2] / n),
+                       });
+            }
+        }
+        im
+    }
+
+    // resolve_depth returns the depth of each pixel's nearest sample, for post-processing passes
+    // that need a single sampled depth buffer.
+    pub fn resolve_depth(&self) -> draw::DepthBuffer {
+        let mut z_buffer = draw::DepthBuffer::new(self.w, self.h);
+        for y in 0..self.h {
+            for x in 0..self.w {
+                // Larger depths are closer to the camera.
+                let z = (0..self.samples).map(|s| self.depth(x, y, s)).fold(f32::MIN, f32::max);
+                z_buffer.set(x, y, z);
+            }
+        }
+        z_buffer
+    }
+}
@@ -0,0 +1,106 @@
+use color;
+use draw;
+use hdr;
+use shader;
+
+// OitBuffer accumulates transparent fragments for weighted blended order-independent transparency
+// (McGuire and Bavoil 2013).  Every fragment adds its premultiplied color, weighted to favor
+// fragments near the camera, to a running sum, and multiplies the fraction of the background still
+// visible by 1 - alpha.  Both are independent of the order fragments arrive in, so intersecting and
+// unsorted transparent faces composite the same however they're drawn.  The result is an
+// approximation, exact for a single layer and close for layers of similar opacity.
+pub struct OitBuffer {
+    pub w: usize,
+    pub h: usize,
+    // Weighted sum of premultiplied linear color and alpha, RGBA per pixel.
+    accum: Vec<f32>,
+    // Product of 1 - alpha of every fragment, the fraction of the background still visible.
+    revealage: Vec<f32>,
+}
+
+impl OitBuffer {
+    pub fn new(w: usize, h: usize) -> Self {
+        OitBuffer {
+            w: w,
+            h: h,
+            accum: vec![0.; w * h * 4],
+            revealage: vec![1.; w * h],
+        }
+    }
+
+    // add accumulates linear fragment c at x,y with screen space depth z, as written to a
+    // draw::DepthBuffer.
+    pub fn add(&mut self, x: usize, y: usize, z: f32, c: hdr::Rgba) {
+        if x >= self.w || y >= self.h {
+            error!("Out of bounds add fragment {},{} size {}x{}",
+                   x,
+                   y,
+                   self.w,
+                   self.h);
+            return;
+        }
+        // Larger depths are closer to the camera, d is 0 at the near plane and 1 at the far one.
+        let d = (1. - z / shader::DEPTH_RESOLUTION).max(0.).min(1.);
+        let w = c.a * (3e3 * (1. - d).powi(3)).max(1e-2);
+        let off = (x + y * self.w) * 4;
+        self.accum[off + 0] += c.r * c.a * w;
+        self.accum[off + 1] += c.g * c.a * w;
+        self.accum[off + 2] += c.b * c.a * w;
+        self.accum[off + 3] += c.a * w;
+        self.revealage[x + y * self.w] *= 1. - c.a;
+    }
+
+    // resolved returns the average transparent color at x,y and how much of what's behind shows
+    // through it.
+    fn resolved(&self, x: usize, y: usize) -> ([f32; 3], f32) {
+        let off = (x + y * self.w) * 4;
+        let a = self.accum[off + 3].max(1e-5);
+        ([self.accum[off] / a, self.accum[off + 1] / a, self.accum[off + 2] / a],
+         self.revealage[x + y * self.w])
+    }
+
+    // composite blends the accumulated transparent fragments over im, which holds the opaque
+    // scene.  Blending is done in linear space.
+    pub fn composite(&self, im: &mut draw::Image) {
+        for y in 0..self.h {
+            for x in 0..self.w {
+                let (c, r) = self.resolved(x, y);
+                if r == 1. {
+                    continue;
+                }
+                let dst = im.get(x, y);
+                let mix = |d: u8, s: f32| {
+                    color::linear_to_srgb(s * (1. - r) + color::srgb_to_linear(d) * r)
+                };
+                im.set(x,
+                       y,
+                       draw::RGB {
+                           r: mix(dst.r, c[0]),
+                           g: mix(dst.g, c[1]),
+                           b: mix(dst.b, c[2]),
+                       });
+            }
+        }
+    }
+
+    // composite_hdr is composite for an hdr::HdrImage.
+    pub fn composite_hdr(&self, im: &mut hdr::HdrImage) {
+        for y in 0..self.h {
+            for x in 0..self.w {
+                let (c, r) = self.resolved(x, y);
+                if r == 1. {
+                    continue;
+                }
+                let dst = im.get(x, y);
+                im.set(x,
+                       y,
+                       hdr::Rgba {
+                           r: c[0] * (1. - r) + dst.r * r,
+                           g: c[1] * (1. - r) + dst.g * r,
+                           b: c[2] * (1. - r) + dst.b * r,
+                           a: (1. - r) + dst.a * r,
+                       });
+            }
+        }
+    }
+}
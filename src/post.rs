@@ -0,0 +1,354 @@
+use std::f32;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+use draw;
+use math;
+use shader::World;
+
+// Width and height of the tile of random rotations repeated across the image by Ssao, the blur
+// pass averages over the same size to hide the pattern.
+const NOISE_SIZE: usize = 4;
+
+// What Ssao writes to the image.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SsaoOutput {
+    // Darken the image by the occlusion.
+    Composite,
+    // Replace the image with the ambient term, white unoccluded and black fully occluded.
+    AoOnly,
+}
+
+// Ssao is a screen space ambient occlusion post-process.  For each pixel a hemisphere of sample
+// points around the surface, reconstructed from the depth buffer of the main pass, is tested
+// against the depth buffer.  The fraction of samples behind other geometry is the occlusion.
+// Sample kernels are randomly rotated per pixel from a small noise tile and the result blurred
+// over the tile to trade banding for noise and then remove the noise.
+pub struct Ssao {
+    // Radius of the sample hemisphere, in pixels.
+    radius: f32,
+    samples: usize,
+    // How much full occlusion darkens the image, 0 leaves it unchanged, 1 makes it black.
+    strength: f32,
+    // Depth, in pixels, a sample must be behind the depth buffer to count as occluded.
+    bias: f32,
+    output: SsaoOutput,
+    seed: [u32; 4],
+}
+
+impl Ssao {
+    pub fn new() -> Self {
+        Ssao {
+            radius: 16.,
+            samples: 16,
+            strength: 1.,
+            bias: 0.5,
+            output: SsaoOutput::Composite,
+            seed: [0x193a_6754, 0xa8a7_d469, 0x9783_0e05, 0x113b_a7bb],
+        }
+    }
+
+    pub fn set_radius(&mut self, radius: f32) {
+        self.radius = radius;
+    }
+
+    // Sets the number of samples per pixel, at least one.
+    pub fn set_samples(&mut self, samples: usize) {
+        self.samples = samples.max(1);
+    }
+
+    pub fn set_strength(&mut self, strength: f32) {
+        self.strength = strength;
+    }
+
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias;
+    }
+
+    pub fn set_output(&mut self, output: SsaoOutput) {
+        self.output = output;
+    }
+
+    // apply computes the ambient occlusion of z_buffer, rendered with world, and writes it to im
+    // as selected by set_output.  im and z_buffer must be the same size.
+    pub fn apply(&self, world: &World, im: &mut draw::Image, z_buffer: &draw::DepthBuffer) {
+        assert_eq!((im.w, im.h), (z_buffer.w, z_buffer.h), "image and depth sizes differ");
+        let ao = self.occlusion(world, z_buffer);
+        let ao = blur(&ao, z_buffer);
+        for y in 0..im.h {
+            for x in 0..im.w {
+                let a = ao[x + y * im.w];
+                match self.output {
+                    SsaoOutput::Composite => {
+                        let k = 1. - self.strength * (1. - a);
+                        let c = im.get(x, y);
+                        im.set(x,
+                               y,
+                               draw::RGB {
+                                   r: (c.r as f32 * k) as u8,
+                                   g: (c.g as f32 * k) as u8,
+                                   b: (c.b as f32 * k) as u8,
+                               });
+                    }
+                    SsaoOutput::AoOnly => {
+                        let v = (a * 255.) as u8;
+                        im.set(x, y, draw::RGB { r: v, g: v, b: v });
+                    }
+                }
+            }
+        }
+    }
+
+    // occlusion returns the unblurred ambient term per pixel, 1 where nothing was drawn.
+    fn occlusion(&self, world: &World, z_buffer: &draw::DepthBuffer) -> Vec<f32> {
+        let mut rng = XorShiftRng::from_seed(self.seed);
+        // Points in the z up unit hemisphere, more of them close to the center.
+        let mut kernel = Vec::with_capacity(self.samples);
+        for i in 0..self.samples {
+            let v = math::Vec3f {
+                    x: rng.gen::<f32>() * 2. - 1.,
+                    y: rng.gen::<f32>() * 2. - 1.,
+                    z: rng.gen::<f32>(),
+                }
+                .normalize();
+            let t = i as f32 / self.samples as f32;
+            kernel.push(v.scale(rng.gen::<f32>() * (0.1 + 0.9 * t * t)));
+        }
+        // Random rotations about the surface normal.
+        let mut noise = Vec::with_capacity(NOISE_SIZE * NOISE_SIZE);
+        for _ in 0..NOISE_SIZE * NOISE_SIZE {
+            noise.push(math::Vec3f {
+                x: rng.gen::<f32>() * 2. - 1.,
+                y: rng.gen::<f32>() * 2. - 1.,
+                z: 0.,
+            });
+        }
+
+        let depth = DepthView::new(world, z_buffer);
+        let (w, h) = (z_buffer.w, z_buffer.h);
+        let mut ao = vec![1.; w * h];
+        for y in 0..h {
+            for x in 0..w {
+                let p = match depth.position(x as i64, y as i64) {
+                    Some(p) => p,
+                    None => continue,
+                };
+                let n = depth.normal(x as i64, y as i64, p);
+                // Gram-Schmidt the noise vector into a tangent frame around n.
+                let r = noise[x % NOISE_SIZE + (y % NOISE_SIZE) * NOISE_SIZE];
+                let t = r - n.scale(math::dot(r, n));
+                if t.length() < 1e-6 {
+                    continue;
+                }
+                let t = t.normalize();
+                let b = math::cross(n, t);
+
+                let mut occluded = 0.;
+                for k in &kernel {
+                    let s = p + (t.scale(k.x) + b.scale(k.y) + n.scale(k.z)).scale(self.radius);
+                    let scene = match depth.position(s.x.floor() as i64, s.y.floor() as i64) {
+                        Some(q) => q.z,
+                        None => continue,
+                    };
+                    // Larger depths are closer to the camera.
+                    if scene >= s.z + self.bias {
+                        // Ignore occluders much further away than the radius, they're a different
+                        // surface in front of this one, not a crevice.
+                        occluded += (self.radius / (p.z - scene).abs()).min(1.);
+                    }
+                }
+                ao[x + y * w] = 1. - occluded / kernel.len() as f32;
+            }
+        }
+        ao
+    }
+}
+
+// DepthView reconstructs screen space positions, with depth scaled to pixels, from a DepthBuffer.
+struct DepthView<'a> {
+    z_buffer: &'a draw::DepthBuffer,
+    // Pixels per unit of depth.
+    scale: f32,
+}
+
+impl<'a> DepthView<'a> {
+    fn new(world: &World, z_buffer: &'a draw::DepthBuffer) -> Self {
+        DepthView {
+            z_buffer: z_buffer,
+            scale: world.viewport[(0, 0)] / world.viewport[(2, 2)],
+        }
+    }
+
+    // position returns the surface point at the center of pixel x,y, or None if nothing was drawn
+    // there or it's outside the buffer.
+    fn position(&self, x: i64, y: i64) -> Option<math::Vec3f> {
+        if x < 0 || y < 0 || x >= self.z_buffer.w as i64 || y >= self.z_buffer.h as i64 {
+            return None;
+        }
+        let z = self.z_buffer.get(x as usize, y as usize);
+        if z == f32::MIN {
+            return None;
+        }
+        Some(math::Vec3f {
+            x: x as f32 + 0.5,
+            y: y as f32 + 0.5,
+            z: z * self.scale,
+        })
+    }
+
+    // normal estimates the surface normal at p, the position of pixel x,y, from its neighbors.  Of
+    // the differences on either side the smaller is used so depth discontinuities don't bend it.
+    fn normal(&self, x: i64, y: i64, p: math::Vec3f) -> math::Vec3f {
+        let dzdx = self.slope(p, self.position(x - 1, y), self.position(x + 1, y));
+        let dzdy = self.slope(p, self.position(x, y - 1), self.position(x, y + 1));
+        math::Vec3f {
+                x: -dzdx,
+                y: -dzdy,
+                z: 1.,
+            }
+            .normalize()
+    }
+
+    fn slope(&self, p: math::Vec3f, prev: Option<math::Vec3f>, next: Option<math::Vec3f>) -> f32 {
+        let before = prev.map(|q| p.z - q.z);
+        let after = next.map(|q| q.z - p.z);
+        match (before, after) {
+            (Some(b), Some(a)) => if b.abs() < a.abs() { b } else { a },
+            (Some(b), None) => b,
+            (None, Some(a)) => a,
+            (None, None) => 0.,
+        }
+    }
+}
+
+// blur box filters ao over NOISE_SIZE x NOISE_SIZE pixels, skipping pixels with nothing drawn.
+fn blur(ao: &[f32], z_buffer: &draw::DepthBuffer) -> Vec<f32> {
+    let (w, h) = (z_buffer.w, z_buffer.h);
+    let half = (NOISE_SIZE / 2) as i64;
+    let mut out = vec![1.; w * h];
+    for y in 0..h as i64 {
+        for x in 0..w as i64 {
+            if z_buffer.get(x as usize, y as usize) == f32::MIN {
+                continue;
+            }
+            let (mut sum, mut count) = (0., 0);
+            for by in y - half..y - half + NOISE_SIZE as i64 {
+                for bx in x - half..x - half + NOISE_SIZE as i64 {
+                    if bx < 0 || by < 0 || bx >= w as i64 || by >= h as i64 {
+                        continue;
+                    }
+                    if z_buffer.get(bx as usize, by as usize) == f32::MIN {
+                        continue;
+                    }
+                    sum += ao[bx as usize + by as usize * w];
+                    count += 1;
+                }
+            }
+            out[x as usize + y as usize * w] = sum / count as f32;
+        }
+    }
+    out
+}
+
+// Outline draws lines along silhouettes and creases, found as discontinuities in the depth buffer
+// and in a normal buffer rendered with shader::NormalShader from the same view.
+pub struct Outline {
+    // Depth difference, in pixels, between neighbors that makes an edge.
+    depth_threshold: f32,
+    // Angle, in radians, between neighboring normals that makes an edge.
+    normal_threshold: f32,
+    // Pixels either side of an edge that are drawn.
+    width: usize,
+    color: draw::RGB,
+}
+
+impl Outline {
+    pub fn new() -> Self {
+        Outline {
+            depth_threshold: 8.,
+            normal_threshold: 0.8,
+            width: 1,
+            color: draw::RGB { r: 0, g: 0, b: 0 },
+        }
+    }
+
+    pub fn set_depth_threshold(&mut self, depth_threshold: f32) {
+        self.depth_threshold = depth_threshold;
+    }
+
+    pub fn set_normal_threshold(&mut self, normal_threshold: f32) {
+        self.normal_threshold = normal_threshold;
+    }
+
+    pub fn set_width(&mut self, width: usize) {
+        self.width = width;
+    }
+
+    pub fn set_color(&mut self, color: draw::RGB) {
+        self.color = color;
+    }
+
+    // apply draws the outlines of the scene in z_buffer and normals, rendered with world, onto im.
+    pub fn apply(&self,
+                 world: &World,
+                 im: &mut draw::Image,
+                 z_buffer: &draw::DepthBuffer,
+                 normals: &draw::Image) {
+        let depth = DepthView::new(world, z_buffer);
+        let (w, h) = (z_buffer.w as i64, z_buffer.h as i64);
+        let cos_threshold = self.normal_threshold.cos();
+        let mut edge = vec![false; (w * h) as usize];
+        for y in 0..h {
+            for x in 0..w {
+                let p = depth.position(x, y);
+                // Compare against the right and upper neighbors, every pair is visited once.
+                for &(nx, ny) in &[(x + 1, y), (x, y + 1)] {
+                    if nx >= w || ny >= h {
+                        continue;
+                    }
+                    let is_edge = match (p, depth.position(nx, ny)) {
+                        (None, None) => false,
+                        // Silhouette against the background.
+                        (Some(_), None) | (None, Some(_)) => true,
+                        (Some(a), Some(b)) => {
+                            (a.z - b.z).abs() > self.depth_threshold ||
+                            math::dot(decode_normal(normals.get(x as usize, y as usize)),
+                                      decode_normal(normals.get(nx as usize, ny as usize))) <
+                            cos_threshold
+                        }
+                    };
+                    if is_edge {
+                        edge[(x + y * w) as usize] = true;
+                    }
+                }
+            }
+        }
+
+        let r = self.width as i64 - 1;
+        for y in 0..h {
+            for x in 0..w {
+                if !edge[(x + y * w) as usize] {
+                    continue;
+                }
+                for dy in -r..r + 1 {
+                    for dx in -r..r + 1 {
+                        let (ox, oy) = (x + dx, y + dy);
+                        if ox >= 0 && oy >= 0 && ox < w && oy < h {
+                            im.set(ox as usize, oy as usize, self.color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// decode_normal undoes the color encoding of shader::NormalShader.
+fn decode_normal(c: draw::RGB) -> math::Vec3f {
+    math::Vec3f {
+            x: c.r as f32 / 127.5 - 1.,
+            y: c.g as f32 / 127.5 - 1.,
+            z: c.b as f32 / 127.5 - 1.,
+        }
+        .normalize()
+}
@@ -0,0 +1,388 @@
+use math;
+
+// Number of fractional bits in the fixed-point screen coordinates used by the rasterizer.
+pub const SUBPIXEL_BITS: i64 = 8;
+const SUBPIXEL_ONE: i64 = 1 << SUBPIXEL_BITS;
+const SUBPIXEL_HALF: i64 = SUBPIXEL_ONE / 2;
+
+// Screen space point in fixed-point, SUBPIXEL_BITS of fraction.
+#[derive(Copy, Clone, Debug)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+impl Point {
+    fn from_vec3f(v: math::Vec3f) -> Self {
+        Point {
+            x: (v.x * SUBPIXEL_ONE as f32).round() as i64,
+            y: (v.y * SUBPIXEL_ONE as f32).round() as i64,
+        }
+    }
+
+    // Fixed-point position of the center of pixel x,y.
+    fn pixel_center(x: i64, y: i64) -> Self {
+        Point {
+            x: (x << SUBPIXEL_BITS) + SUBPIXEL_HALF,
+            y: (y << SUBPIXEL_BITS) + SUBPIXEL_HALF,
+        }
+    }
+}
+
+// edge evaluates the edge function of a->b at p. It is positive when p is to the left of a->b,
+// zero when p is on the line and negative when p is to the right.  All inputs are integers so the
+// result is exact.
+fn edge(a: Point, b: Point, p: Point) -> i64 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+// is_top_left returns true if the edge a->b of a counter-clockwise triangle is a top or left edge.
+// Screen space has y pointing up, so walking counter-clockwise a top edge runs right to left and a
+// left edge runs downward.  Pixel centers exactly on a top or left edge belong to the triangle,
+// those on any other edge do not, so a pixel on an edge shared by two triangles is drawn once.
+fn is_top_left(a: Point, b: Point) -> bool {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    dy < 0 || (dy == 0 && dx < 0)
+}
+
+// Bias added to an edge function so a single '>= 0' test implements the top-left fill rule.
+fn bias(a: Point, b: Point) -> i64 {
+    if is_top_left(a, b) { 0 } else { -1 }
+}
+
+// Inclusive pixel bounds (x_min, y_min, x_max, y_max) of the pixel centers that could be covered
+// by the fixed-point triangle v, clipped to region (x, y, w, h). Returns None if nothing is left.
+fn pixel_bounds(v: &[Point; 3],
+                region: (usize, usize, usize, usize))
+                -> Option<(i64, i64, i64, i64)> {
+    use std::cmp::{max, min};
+    let (rx, ry, rw, rh) = (region.0 as i64, region.1 as i64, region.2 as i64, region.3 as i64);
+    let x_min = max((min(min(v[0].x, v[1].x), v[2].x) - SUBPIXEL_HALF) >> SUBPIXEL_BITS,
+                    rx);
+    let x_max = min((max(max(v[0].x, v[1].x), v[2].x) - SUBPIXEL_HALF) >> SUBPIXEL_BITS,
+                    rx + rw - 1);
+    let y_min = max((min(min(v[0].y, v[1].y), v[2].y) - SUBPIXEL_HALF) >> SUBPIXEL_BITS,
+                    ry);
+    let y_max = min((max(max(v[0].y, v[1].y), v[2].y) - SUBPIXEL_HALF) >> SUBPIXEL_BITS,
+                    ry + rh - 1);
+    if x_min > x_max || y_min > y_max {
+        return None;
+    }
+    Some((x_min, y_min, x_max, y_max))
+}
+
+// bounds returns the inclusive pixel bounds (x_min, y_min, x_max, y_max) of the screen space
+// triangle tri in a w x h image, or None if it can't cover any pixel.  Every fragment rasterize
+// emits for tri lies within these bounds.
+pub fn bounds(tri: &[math::Vec3f; 3], w: usize, h: usize) -> Option<(usize, usize, usize, usize)> {
+    let v = [Point::from_vec3f(tri[0]),
+             Point::from_vec3f(tri[1]),
+             Point::from_vec3f(tri[2])];
+    pixel_bounds(&v, (0, 0, w, h))
+        .map(|(x_min, y_min, x_max, y_max)| {
+            (x_min as usize, y_min as usize, x_max as usize, y_max as usize)
+        })
+}
+
+// Pixels along each side of the square blocks rasterize tests for early rejection.
+const BLOCK_SIZE: i64 = 8;
+
+// Offsets of the four lanes of a 2x2 quad of pixels.
+const QUAD_X: [i64; 4] = [0, 1, 0, 1];
+const QUAD_Y: [i64; 4] = [0, 0, 1, 1];
+
+// EdgeFn is an edge function set up for incremental evaluation in whole pixel steps, with the
+// top-left bias folded in so a pixel is inside the edge when the value is >= 0.
+#[derive(Copy, Clone, Debug)]
+struct EdgeFn {
+    // Value at the center of pixel 0,0.
+    origin: i64,
+    // Change in value moving one pixel in x and y.
+    step_x: i64,
+    step_y: i64,
+    bias: i64,
+}
+
+impl EdgeFn {
+    fn new(a: Point, b: Point) -> Self {
+        let bias = bias(a, b);
+        EdgeFn {
+            origin: edge(a, b, Point::pixel_center(0, 0)) + bias,
+            step_x: -(b.y - a.y) << SUBPIXEL_BITS,
+            step_y: (b.x - a.x) << SUBPIXEL_BITS,
+            bias: bias,
+        }
+    }
+
+    fn at(&self, x: i64, y: i64) -> i64 {
+        self.origin + x * self.step_x + y * self.step_y
+    }
+
+    // Values of the edge function for the four pixels of the 2x2 quad with top-left pixel x,y.
+    fn quad(&self, x: i64, y: i64) -> [i64; 4] {
+        let base = self.at(x, y);
+        let mut lanes = [0; 4];
+        for l in 0..4 {
+            lanes[l] = base + QUAD_X[l] * self.step_x + QUAD_Y[l] * self.step_y;
+        }
+        lanes
+    }
+
+    // Returns true if no pixel center in the block with top-left pixel x,y is inside the edge. The
+    // edge function is linear so checking the block's corner pixels is enough.
+    fn rejects_block(&self, x: i64, y: i64) -> bool {
+        let last = BLOCK_SIZE - 1;
+        self.at(x, y) < 0 && self.at(x + last, y) < 0 && self.at(x, y + last) < 0 &&
+        self.at(x + last, y + last) < 0
+    }
+}
+
+// rasterize calls frag with the pixel coordinates and barycentric coordinates of every pixel in a
+// w x h image whose center is covered by the screen space triangle tri.  Coverage is computed on
+// SUBPIXEL_BITS fixed-point coordinates with a top-left fill rule, so triangles sharing an edge
+// never leave gaps between them and never both cover the same pixel.  Both windings are
+// rasterized, culling is left to the caller.
+//
+// Edge functions are set up once per triangle and stepped across the bounding box in 8x8 blocks,
+// empty blocks are skipped after testing their corners and the rest are walked as 2x2 quads so the
+// per-lane arithmetic can be vectorized.  Fragments within a block are emitted in quad order.
+pub fn rasterize<F>(tri: &[math::Vec3f; 3], w: usize, h: usize, frag: F)
+    where F: FnMut(usize, usize, math::Vec3f)
+{
+    rasterize_region(tri, (0, 0, w, h), frag)
+}
+
+// rasterize_region is rasterize restricted to the pixels in region, given as (x, y, w, h) in image
+// coordinates.  Coverage and barycentric coordinates don't depend on the region, so rendering an
+// image in pieces produces the same fragments as rendering it whole.
+pub fn rasterize_region<F>(tri: &[math::Vec3f; 3],
+                           region: (usize, usize, usize, usize),
+                           mut frag: F)
+    where F: FnMut(usize, usize, math::Vec3f)
+{
+    let mut v = [Point::from_vec3f(tri[0]),
+                 Point::from_vec3f(tri[1]),
+                 Point::from_vec3f(tri[2])];
+    let mut area = edge(v[0], v[1], v[2]);
+    if area == 0 {
+        return;
+    }
+    // Make the triangle counter-clockwise so the inside is to the left of every edge, remember to
+    // swap the barycentric coordinates back.
+    let flipped = area < 0;
+    if flipped {
+        v.swap(1, 2);
+        area = -area;
+    }
+
+    let (x_min, y_min, x_max, y_max) = match pixel_bounds(&v, region) {
+        Some(b) => b,
+        None => return,
+    };
+    debug!("Tri BBox x {},{} y {},{}", x_min, x_max, y_min, y_max);
+
+    let edges = [EdgeFn::new(v[1], v[2]), EdgeFn::new(v[2], v[0]), EdgeFn::new(v[0], v[1])];
+    let inv_area = 1. / area as f32;
+    // Blocks are aligned to the image, not the bounding box, so neighboring triangles walk the
+    // same blocks.
+    let mut by = y_min & !(BLOCK_SIZE - 1);
+    while by <= y_max {
+        let mut bx = x_min & !(BLOCK_SIZE - 1);
+        while bx <= x_max {
+            if edges.iter().any(|e| e.rejects_block(bx, by)) {
+                bx += BLOCK_SIZE;
+                continue;
+            }
+            for qy in 0..BLOCK_SIZE / 2 {
+                for qx in 0..BLOCK_SIZE / 2 {
+                    let (x, y) = (bx + qx * 2, by + qy * 2);
+                    let w0 = edges[0].quad(x, y);
+                    let w1 = edges[1].quad(x, y);
+                    let w2 = edges[2].quad(x, y);
+                    for l in 0..4 {
+                        // The sign bit of the or is set if any of the edge values is negative.
+                        if (w0[l] | w1[l] | w2[l]) < 0 {
+                            continue;
+                        }
+                        let (px, py) = (x + QUAD_X[l], y + QUAD_Y[l]);
+                        if px < x_min || px > x_max || py < y_min || py > y_max {
+                            continue;
+                        }
+                        let b0 = (w0[l] - edges[0].bias) as f32 * inv_area;
+                        let b1 = (w1[l] - edges[1].bias) as f32 * inv_area;
+                        let b2 = (w2[l] - edges[2].bias) as f32 * inv_area;
+                        let bc = math::Vec3f {
+                            x: b0,
+                            y: if flipped { b2 } else { b1 },
+                            z: if flipped { b1 } else { b2 },
+                        };
+                        frag(px as usize, py as usize, bc);
+                    }
+                }
+            }
+            bx += BLOCK_SIZE;
+        }
+        by += BLOCK_SIZE;
+    }
+}
+
+// Standard 2x, 4x and 8x multisample positions, as used by Direct3D, in sixteenths of a pixel from
+// the pixel center.
+const SAMPLES_2X: [(i64, i64); 2] = [(4, 4), (-4, -4)];
+const SAMPLES_4X: [(i64, i64); 4] = [(-2, -6), (6, -2), (-6, 2), (2, 6)];
+const SAMPLES_8X: [(i64, i64); 8] = [(1, -3), (-1, 3), (5, 1), (-3, -5), (-5, 5), (-7, -1), (3, 7),
+                                     (7, -7)];
+
+// sample_pattern returns the sample positions, in sixteenths of a pixel from the pixel center, for
+// 1, 2, 4 or 8 samples per pixel.
+pub fn sample_pattern(samples: usize) -> &'static [(i64, i64)] {
+    const SAMPLES_1X: [(i64, i64); 1] = [(0, 0)];
+    match samples {
+        1 => &SAMPLES_1X,
+        2 => &SAMPLES_2X,
+        4 => &SAMPLES_4X,
+        8 => &SAMPLES_8X,
+        _ => panic!("unsupported sample count {}", samples),
+    }
+}
+
+// rasterize_samples is rasterize with coverage tested at several samples per pixel, positioned by
+// pattern as returned by sample_pattern, for multisample anti-aliasing.  frag is called once for
+// every pixel in region with at least one sample covered, with a mask of the covered samples, bit
+// i for pattern[i], and the barycentric coordinates at the pixel center.  The center may be
+// outside the triangle, the coordinates are then extrapolated.  The same top-left fill rule
+// applies to each sample so no sample is covered twice or missed along shared edges.
+pub fn rasterize_samples<F>(tri: &[math::Vec3f; 3],
+                            region: (usize, usize, usize, usize),
+                            pattern: &[(i64, i64)],
+                            mut frag: F)
+    where F: FnMut(usize, usize, u32, math::Vec3f)
+{
+    use std::cmp::{max, min};
+    let mut v = [Point::from_vec3f(tri[0]),
+                 Point::from_vec3f(tri[1]),
+                 Point::from_vec3f(tri[2])];
+    let mut area = edge(v[0], v[1], v[2]);
+    if area == 0 {
+        return;
+    }
+    let flipped = area < 0;
+    if flipped {
+        v.swap(1, 2);
+        area = -area;
+    }
+    // Any pixel the triangle touches may have a covered sample.
+    let (rx, ry, rw, rh) = (region.0 as i64, region.1 as i64, region.2 as i64, region.3 as i64);
+    let x_min = max(min(min(v[0].x, v[1].x), v[2].x) >> SUBPIXEL_BITS, rx);
+    let x_max = min(max(max(v[0].x, v[1].x), v[2].x) >> SUBPIXEL_BITS, rx + rw - 1);
+    let y_min = max(min(min(v[0].y, v[1].y), v[2].y) >> SUBPIXEL_BITS, ry);
+    let y_max = min(max(max(v[0].y, v[1].y), v[2].y) >> SUBPIXEL_BITS, ry + rh - 1);
+
+    let edges = [EdgeFn::new(v[1], v[2]), EdgeFn::new(v[2], v[0]), EdgeFn::new(v[0], v[1])];
+    // Change in each edge function for a sample offset in sixteenths of a pixel.  Steps are whole
+    // multiples of SUBPIXEL_ONE so this is exact.
+    let offset = |e: &EdgeFn, s: (i64, i64)| (s.0 * e.step_x + s.1 * e.step_y) / 16;
+    let inv_area = 1. / area as f32;
+    for y in y_min..y_max + 1 {
+        for x in x_min..x_max + 1 {
+            let w = [edges[0].at(x, y), edges[1].at(x, y), edges[2].at(x, y)];
+            let mut mask = 0;
+            for (i, &s) in pattern.iter().enumerate() {
+                if (0..3).all(|e| w[e] + offset(&edges[e], s) >= 0) {
+                    mask |= 1 << i;
+                }
+            }
+            if mask == 0 {
+                continue;
+            }
+            let b0 = (w[0] - edges[0].bias) as f32 * inv_area;
+            let b1 = (w[1] - edges[1].bias) as f32 * inv_area;
+            let b2 = (w[2] - edges[2].bias) as f32 * inv_area;
+            let bc = math::Vec3f {
+                x: b0,
+                y: if flipped { b2 } else { b1 },
+                z: if flipped { b1 } else { b2 },
+            };
+            frag(x as usize, y as usize, mask, bc);
+        }
+    }
+}
+
+// derivatives returns the change in the barycentric coordinates rasterize passes to frag when
+// moving one pixel right and one pixel down, or None for a degenerate triangle.  Barycentric
+// coordinates are linear in screen space so these are the same for every fragment of tri, shaders
+// use them to find how quickly interpolated values such as texture coordinates change across the
+// screen.
+pub fn derivatives(tri: &[math::Vec3f; 3]) -> Option<(math::Vec3f, math::Vec3f)> {
+    let v = [Point::from_vec3f(tri[0]),
+             Point::from_vec3f(tri[1]),
+             Point::from_vec3f(tri[2])];
+    let area = edge(v[0], v[1], v[2]);
+    if area == 0 {
+        return None;
+    }
+    let edges = [EdgeFn::new(v[1], v[2]), EdgeFn::new(v[2], v[0]), EdgeFn::new(v[0], v[1])];
+    let inv_area = 1. / area as f32;
+    Some((math::Vec3f {
+              x: edges[0].step_x as f32 * inv_area,
+              y: edges[1].step_x as f32 * inv_area,
+              z: edges[2].step_x as f32 * inv_area,
+          },
+          math::Vec3f {
+              x: edges[0].step_y as f32 * inv_area,
+              y: edges[1].step_y as f32 * inv_area,
+              z: edges[2].step_y as f32 * inv_area,
+          }))
+}
+
+// rasterize_reference is the straightforward version of rasterize, evaluating all three edge
+// functions from scratch at every pixel in the bounding box in scanline order. It produces exactly
+// the same fragments as rasterize and is kept for testing and benchmarking rasterize against.
+pub fn rasterize_reference<F>(tri: &[math::Vec3f; 3], w: usize, h: usize, mut frag: F)
+    where F: FnMut(usize, usize, math::Vec3f)
+{
+    let mut v = [Point::from_vec3f(tri[0]),
+                 Point::from_vec3f(tri[1]),
+                 Point::from_vec3f(tri[2])];
+    let mut area = edge(v[0], v[1], v[2]);
+    if area == 0 {
+        return;
+    }
+    // Make the triangle counter-clockwise so the inside is to the left of every edge, remember to
+    // swap the barycentric coordinates back.
+    let flipped = area < 0;
+    if flipped {
+        v.swap(1, 2);
+        area = -area;
+    }
+
+    let (x_min, y_min, x_max, y_max) = match pixel_bounds(&v, (0, 0, w, h)) {
+        Some(b) => b,
+        None => return,
+    };
+    debug!("Tri BBox x {},{} y {},{}", x_min, x_max, y_min, y_max);
+
+    let bias0 = bias(v[1], v[2]);
+    let bias1 = bias(v[2], v[0]);
+    let bias2 = bias(v[0], v[1]);
+    let inv_area = 1. / area as f32;
+    for y in y_min..y_max + 1 {
+        for x in x_min..x_max + 1 {
+            let p = Point::pixel_center(x, y);
+            let w0 = edge(v[1], v[2], p);
+            let w1 = edge(v[2], v[0], p);
+            let w2 = edge(v[0], v[1], p);
+            if w0 + bias0 < 0 || w1 + bias1 < 0 || w2 + bias2 < 0 {
+                // Outside the triangle.
+                continue;
+            }
+            let (b1, b2) = (w1 as f32 * inv_area, w2 as f32 * inv_area);
+            let bc = math::Vec3f {
+                x: w0 as f32 * inv_area,
+                y: if flipped { b2 } else { b1 },
+                z: if flipped { b1 } else { b2 },
+            };
+            frag(x as usize, y as usize, bc);
+        }
+    }
+}
@@ -0,0 +1,620 @@
+use std::cmp::{Ordering, min};
+
+use scoped_threadpool::Pool;
+
+use draw;
+use hdr;
+use math;
+use msaa;
+use oit;
+use raster;
+use shader::{self, Shader, World};
+use wavefront;
+
+// Default width and height of the tiles the screen is split into when rendering on several threads.
+static DEFAULT_TILE_SIZE: usize = 64;
+
+// How Renderer combines a fragment's color with the color already in the output, weighted by the
+// shader's alpha.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Blend {
+    // Overwrite the output, for opaque objects.
+    Replace,
+    // src * a + dst * (1 - a).
+    Alpha,
+    // dst + src * a.
+    Additive,
+    // dst * (src * a + 1 - a), tinting what's behind.
+    Multiply,
+}
+
+impl Blend {
+    // mix blends channel src with alpha a into dst, where one is the value of full intensity.
+    fn mix(&self, dst: f32, src: f32, a: f32, one: f32) -> f32 {
+        match *self {
+            Blend::Replace => src,
+            Blend::Alpha => src * a + dst * (1. - a),
+            Blend::Additive => dst + src * a,
+            Blend::Multiply => dst * (src / one * a + 1. - a),
+        }
+    }
+}
+
+// Target is a color buffer Renderer shades into, either an 8-bit draw::Image or a linear
+// hdr::HdrImage.
+pub trait Target: Send + Sized {
+    fn size(&self) -> (usize, usize);
+    // Copy of the w x h region with its corner at x,y, for a tile.
+    fn sub_target(&self, x: usize, y: usize, w: usize, h: usize) -> Self;
+    // Copies all of src into the target with src's corner at x,y.
+    fn blit_target(&mut self, src: &Self, x: usize, y: usize);
+    // shade runs shdr's fragment stage at bc and blends the result into x,y.  It returns false if
+    // the fragment was discarded, by the shader or for alpha below alpha_test.
+    fn shade<S: Shader>(&mut self,
+                        shdr: &S,
+                        world: &World,
+                        x: usize,
+                        y: usize,
+                        bc: math::Vec3f,
+                        blend: Blend,
+                        alpha_test: f32)
+                        -> bool;
+}
+
+// fragment runs shdr's linear fragment stage at bc.  It returns None if the shader discards the
+// fragment or its alpha is below alpha_test.
+fn fragment<S: Shader>(shdr: &S,
+                       world: &World,
+                       bc: math::Vec3f,
+                       alpha_test: f32)
+                       -> Option<hdr::Rgba> {
+    match shdr.fragment_linear(world, bc) {
+        Some(c) if c.a >= alpha_test => Some(c),
+        _ => None,
+    }
+}
+
+impl Target for draw::Image {
+    fn size(&self) -> (usize, usize) {
+        (self.w, self.h)
+    }
+
+    fn sub_target(&self, x: usize, y: usize, w: usize, h: usize) -> Self {
+        self.sub_image(x, y, w, h)
+    }
+
+    fn blit_target(&mut self, src: &Self, x: usize, y: usize) {
+        self.blit(src, x, y)
+    }
+
+    fn shade<S: Shader>(&mut self,
+                        shdr: &S,
+                        world: &World,
+                        x: usize,
+                        y: usize,
+                        bc: math::Vec3f,
+                        blend: Blend,
+                        alpha_test: f32)
+                        -> bool {
+        if blend == Blend::Replace && alpha_test <= 0. {
+            // Alpha is unused, take the shader's sRGB color as is.
+            return match shdr.fragment(world, bc) {
+                Some(c) => {
+                    self.set(x, y, c);
+                    true
+                }
+                None => false,
+            };
+        }
+        let (c, a) = match fragment(shdr, world, bc, alpha_test) {
+            Some(c) => (c.to_srgb(), c.a),
+            None => return false,
+        };
+        if blend == Blend::Replace {
+            self.set(x, y, c);
+            return true;
+        }
+        let dst = self.get(x, y);
+        let mix = |d: u8, s: u8| blend.mix(d as f32, s as f32, a, 255.).max(0.).min(255.) as u8;
+        self.set(x,
+                 y,
+                 draw::RGB {
+                     r: mix(dst.r, c.r),
+                     g: mix(dst.g, c.g),
+                     b: mix(dst.b, c.b),
+                 });
+        true
+    }
+}
+
+impl Target for hdr::HdrImage {
+    fn size(&self) -> (usize, usize) {
+        (self.w, self.h)
+    }
+
+    fn sub_target(&self, x: usize, y: usize, w: usize, h: usize) -> Self {
+        self.sub_image(x, y, w, h)
+    }
+
+    fn blit_target(&mut self, src: &Self, x: usize, y: usize) {
+        self.blit(src, x, y)
+    }
+
+    fn shade<S: Shader>(&mut self,
+                        shdr: &S,
+                        world: &World,
+                        x: usize,
+                        y: usize,
+                        bc: math::Vec3f,
+                        blend: Blend,
+                        alpha_test: f32)
+                        -> bool {
+        let c = match fragment(shdr, world, bc, alpha_test) {
+            Some(c) => c,
+            None => return false,
+        };
+        if blend == Blend::Replace {
+            self.set(x, y, c);
+            return true;
+        }
+        let dst = self.get(x, y);
+        self.set(x,
+                 y,
+                 hdr::Rgba {
+                     r: blend.mix(dst.r, c.r, c.a, 1.),
+                     g: blend.mix(dst.g, c.g, c.a, 1.),
+                     b: blend.mix(dst.b, c.b, c.a, 1.),
+                     // Coverage accumulates as with alpha blending whatever the mode.
+                     a: c.a + dst.a * (1. - c.a),
+                 });
+        true
+    }
+}
+
+// A rectangle of the output being rendered by one thread, with its own copy of the color and depth
+// buffers under it.
+struct Tile<T> {
+    x: usize,
+    y: usize,
+    im: T,
+    z_buffer: draw::DepthBuffer,
+    // Indices of the faces whose bounds overlap the tile, in draw order.
+    faces: Vec<usize>,
+}
+
+// How Renderer draws the edges of faces.
+pub struct Wireframe {
+    pub color: draw::RGB,
+    // Only draw edges of the polygons in the model file, not the diagonals added when splitting
+    // them into triangles.
+    pub polygon_edges_only: bool,
+    // Depth, as a fraction of the depth range, an edge may be behind the depth buffer and still be
+    // drawn when depth testing.  Edges lie on the surface they're tested against, so without it
+    // they flicker in and out.
+    pub depth_bias: f32,
+}
+
+impl Wireframe {
+    pub fn new(color: draw::RGB) -> Self {
+        Wireframe {
+            color: color,
+            polygon_edges_only: false,
+            depth_bias: 0.002,
+        }
+    }
+}
+
+// Renderer draws objects through a Shader into an Image and DepthBuffer.
+//
+// With one thread faces are drawn in order straight into the output.  With more, faces are run
+// through the vertex shader and binned into the tiles their screen space bounds overlap, then
+// tiles are rasterized and shaded in parallel.  Each tile draws its faces in the original order,
+// and coverage doesn't depend on the tile, so the output is identical to the single threaded path.
+pub struct Renderer {
+    threads: usize,
+    tile_size: usize,
+    blend: Blend,
+    alpha_test: f32,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Renderer {
+            threads: 1,
+            tile_size: DEFAULT_TILE_SIZE,
+            blend: Blend::Replace,
+            alpha_test: 0.,
+        }
+    }
+
+    // Sets the number of threads used to shade tiles. 1, the default, renders without tiling.
+    pub fn set_threads(&mut self, threads: usize) {
+        assert!(threads > 0, "need at least one thread");
+        self.threads = threads;
+    }
+
+    pub fn set_tile_size(&mut self, tile_size: usize) {
+        assert!(tile_size > 0, "tiles must be at least one pixel");
+        self.tile_size = tile_size;
+    }
+
+    // Sets how draw combines fragments with the output.  With anything but Blend::Replace faces are
+    // drawn back to front and depth tested without writing depth, so transparent objects should be
+    // drawn after the opaque ones.
+    pub fn set_blend(&mut self, blend: Blend) {
+        self.blend = blend;
+    }
+
+    // Sets the alpha, 0 to 1, below which fragments are discarded, 0 keeps them all.  Discarded
+    // fragments don't write depth, so cutouts such as foliage can be drawn with Blend::Replace.
+    pub fn set_alpha_test(&mut self, threshold: f32) {
+        self.alpha_test = threshold;
+    }
+
+    // draw renders every face of obj with shader into im, depth testing against and updating
+    // z_buffer.
+    pub fn draw<S, T>(&self,
+                      world: &World,
+                      obj: &wavefront::Object,
+                      shader: &S,
+                      im: &mut T,
+                      z_buffer: &mut draw::DepthBuffer)
+        where S: Shader + Clone + Send,
+              T: Target
+    {
+        let mut faces: Vec<wavefront::Face> = obj.into_iter().collect();
+        if self.blend != Blend::Replace {
+            sort_back_to_front(world, &mut faces);
+        }
+        let (blend, alpha_test) = (self.blend, self.alpha_test);
+        if self.threads == 1 {
+            let mut shdr = shader.clone();
+            let (w, h) = im.size();
+            for f in &faces {
+                draw_face(&mut shdr,
+                          world,
+                          f,
+                          (0, 0, w, h),
+                          blend,
+                          alpha_test,
+                          im,
+                          z_buffer);
+            }
+            return;
+        }
+
+        let mut tiles = self.bin(world, &faces, shader, im, z_buffer);
+        info!("Rendering {} faces in {} tiles on {} threads",
+              faces.len(),
+              tiles.len(),
+              self.threads);
+        let mut pool = Pool::new(self.threads as u32);
+        pool.scoped(|scope| {
+            for tile in &mut tiles {
+                if tile.faces.is_empty() {
+                    continue;
+                }
+                let mut shdr = shader.clone();
+                let faces = &faces;
+                scope.execute(move || {
+                    let (w, h) = tile.im.size();
+                    let region = (tile.x, tile.y, w, h);
+                    for &i in &tile.faces {
+                        draw_face(&mut shdr,
+                                  world,
+                                  &faces[i],
+                                  region,
+                                  blend,
+                                  alpha_test,
+                                  &mut tile.im,
+                                  &mut tile.z_buffer);
+                    }
+                });
+            }
+        });
+        for tile in &tiles {
+            im.blit_target(&tile.im, tile.x, tile.y);
+            z_buffer.blit(&tile.z_buffer, tile.x, tile.y);
+        }
+    }
+
+    // draw_msaa renders obj with shader into the multisampled buffer target.  Coverage and depth
+    // are tested at each sample, the shader runs once per pixel at the pixel center and its color
+    // is stored in every covered sample that passed the depth test.  Fragments overwrite the
+    // samples, blending isn't applied but the alpha test is.  Faces are drawn on the calling
+    // thread.
+    pub fn draw_msaa<S>(&self,
+                        world: &World,
+                        obj: &wavefront::Object,
+                        shader: &S,
+                        target: &mut msaa::MsaaBuffer)
+        where S: Shader + Clone
+    {
+        let mut shdr = shader.clone();
+        let (w, h) = (target.w, target.h);
+        let pattern = target.pattern();
+        for f in obj {
+            let tri = shdr.vertex(world, &f);
+            if world.culled(&tri) {
+                continue;
+            }
+            let (dbc_dx, dbc_dy) = match raster::derivatives(&tri) {
+                Some(d) => d,
+                None => continue,
+            };
+            shdr.derivatives(dbc_dx, dbc_dy);
+            // Change in depth per pixel, to find each sample's depth from the center's.
+            let z = math::Vec3f {
+                x: tri[0].z,
+                y: tri[1].z,
+                z: tri[2].z,
+            };
+            let (dz_dx, dz_dy) = (math::dot(z, dbc_dx), math::dot(z, dbc_dy));
+            raster::rasterize_samples(&tri, (0, 0, w, h), pattern, |x, y, mask, bc| {
+                let z_center = math::dot(z, bc);
+                let mut passed = 0;
+                for (s, &(ox, oy)) in pattern.iter().enumerate() {
+                    if mask & (1 << s) == 0 {
+                        continue;
+                    }
+                    let z = z_center + (ox as f32 * dz_dx + oy as f32 * dz_dy) / 16.;
+                    if target.depth(x, y, s) < z {
+                        passed |= 1 << s;
+                    }
+                }
+                if passed == 0 {
+                    return;
+                }
+                // Fragment may say to discard, then don't update depth.
+                let c = if self.alpha_test > 0. {
+                    fragment(&shdr, world, bc, self.alpha_test).map(|c| c.to_srgb())
+                } else {
+                    shdr.fragment(world, bc)
+                };
+                if let Some(c) = c {
+                    for (s, &(ox, oy)) in pattern.iter().enumerate() {
+                        if passed & (1 << s) != 0 {
+                            let z = z_center + (ox as f32 * dz_dx + oy as f32 * dz_dy) / 16.;
+                            target.set_depth(x, y, s, z);
+                            target.set_color(x, y, s, c);
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    // draw_oit renders obj as transparent with shader into oit, in any order, keeping fragments in
+    // front of z_buffer, which should hold the opaque scene's depth and isn't written.  Composite
+    // oit over the opaque scene's image afterwards.  Faces are drawn on the calling thread.
+    pub fn draw_oit<S>(&self,
+                       world: &World,
+                       obj: &wavefront::Object,
+                       shader: &S,
+                       oit: &mut oit::OitBuffer,
+                       z_buffer: &draw::DepthBuffer)
+        where S: Shader + Clone
+    {
+        let mut shdr = shader.clone();
+        let (w, h) = (oit.w, oit.h);
+        for f in obj {
+            let tri = shdr.vertex(world, &f);
+            if world.culled(&tri) {
+                continue;
+            }
+            if let Some((dbc_dx, dbc_dy)) = raster::derivatives(&tri) {
+                shdr.derivatives(dbc_dx, dbc_dy);
+            }
+            raster::rasterize(&tri, w, h, |x, y, bc| {
+                let z = tri[0].z * bc.x + tri[1].z * bc.y + tri[2].z * bc.z;
+                if z_buffer.get(x, y) < z {
+                    if let Some(c) = fragment(&shdr, world, bc, self.alpha_test) {
+                        oit.add(x, y, z, c);
+                    }
+                }
+            });
+        }
+    }
+
+    // draw_depth renders only the depth of obj's faces into z_buffer, for passes such as shadow
+    // maps that don't need color.
+    pub fn draw_depth(&self,
+                      world: &World,
+                      obj: &wavefront::Object,
+                      z_buffer: &mut draw::DepthBuffer) {
+        let (w, h) = (z_buffer.w, z_buffer.h);
+        for f in obj {
+            let tri = [world.vp_p_mv.transform(f.vertices[0]),
+                       world.vp_p_mv.transform(f.vertices[1]),
+                       world.vp_p_mv.transform(f.vertices[2])];
+            if world.culled(&tri) {
+                continue;
+            }
+            raster::rasterize(&tri, w, h, |x, y, bc| {
+                let z = tri[0].z * bc.x + tri[1].z * bc.y + tri[2].z * bc.z;
+                if z_buffer.get(x, y) < z {
+                    z_buffer.set(x, y, z);
+                }
+            });
+        }
+    }
+
+    // draw_wireframe draws every edge of obj's faces, visible or not.
+    pub fn draw_wireframe(&self,
+                          world: &World,
+                          obj: &wavefront::Object,
+                          style: &Wireframe,
+                          im: &mut draw::Image) {
+        self.draw_edges(world, obj, style, im, None);
+    }
+
+    // draw_hidden_line renders the depth of obj into z_buffer, then draws only the edges of obj's
+    // faces that aren't hidden behind other faces.
+    pub fn draw_hidden_line(&self,
+                            world: &World,
+                            obj: &wavefront::Object,
+                            style: &Wireframe,
+                            im: &mut draw::Image,
+                            z_buffer: &mut draw::DepthBuffer) {
+        self.draw_depth(world, obj, z_buffer);
+        self.draw_edges(world, obj, style, im, Some(z_buffer));
+    }
+
+    // draw_overlay draws obj solid with shader, then its visible edges on top.
+    pub fn draw_overlay<S>(&self,
+                           world: &World,
+                           obj: &wavefront::Object,
+                           shader: &S,
+                           style: &Wireframe,
+                           im: &mut draw::Image,
+                           z_buffer: &mut draw::DepthBuffer)
+        where S: Shader + Clone + Send
+    {
+        self.draw(world, obj, shader, im, z_buffer);
+        self.draw_edges(world, obj, style, im, Some(z_buffer));
+    }
+
+    // draw_edges draws the edges of obj's faces, depth tested against z_buffer if given.  Culled
+    // faces are skipped when depth testing, their edges are either hidden or shared with a drawn
+    // face.
+    fn draw_edges(&self,
+                  world: &World,
+                  obj: &wavefront::Object,
+                  style: &Wireframe,
+                  im: &mut draw::Image,
+                  z_buffer: Option<&draw::DepthBuffer>) {
+        let bias = style.depth_bias * shader::DEPTH_RESOLUTION;
+        for f in obj {
+            let tri = [world.vp_p_mv.transform(f.vertices[0]),
+                       world.vp_p_mv.transform(f.vertices[1]),
+                       world.vp_p_mv.transform(f.vertices[2])];
+            if z_buffer.is_some() && world.culled(&tri) {
+                continue;
+            }
+            for i in 0..3 {
+                if style.polygon_edges_only && !f.edges[i] {
+                    continue;
+                }
+                let (a, b) = (tri[i], tri[(i + 1) % 3]);
+                match z_buffer {
+                    Some(z) => depth_tested_line(a, b, style.color, bias, im, z),
+                    None => im.line(&a.to_vec2i(), &b.to_vec2i(), style.color),
+                }
+            }
+        }
+    }
+
+    // bin splits the output into tiles and assigns each visible face to every tile its screen
+    // space bounds overlap.
+    fn bin<S, T>(&self,
+                 world: &World,
+                 faces: &[wavefront::Face],
+                 shader: &S,
+                 im: &T,
+                 z_buffer: &draw::DepthBuffer)
+                 -> Vec<Tile<T>>
+        where S: Shader + Clone,
+              T: Target
+    {
+        let ts = self.tile_size;
+        let (im_w, im_h) = im.size();
+        let (tiles_x, tiles_y) = ((im_w + ts - 1) / ts, (im_h + ts - 1) / ts);
+        let mut tiles = Vec::with_capacity(tiles_x * tiles_y);
+        for ty in 0..tiles_y {
+            for tx in 0..tiles_x {
+                let (x, y) = (tx * ts, ty * ts);
+                let (w, h) = (min(ts, im_w - x), min(ts, im_h - y));
+                tiles.push(Tile {
+                    x: x,
+                    y: y,
+                    im: im.sub_target(x, y, w, h),
+                    z_buffer: z_buffer.sub_buffer(x, y, w, h),
+                    faces: Vec::new(),
+                });
+            }
+        }
+
+        let mut shdr = shader.clone();
+        for (i, f) in faces.iter().enumerate() {
+            let tri = shdr.vertex(world, f);
+            if world.culled(&tri) {
+                continue;
+            }
+            if let Some((x_min, y_min, x_max, y_max)) = raster::bounds(&tri, im_w, im_h) {
+                for ty in y_min / ts..y_max / ts + 1 {
+                    for tx in x_min / ts..x_max / ts + 1 {
+                        tiles[tx + ty * tiles_x].faces.push(i);
+                    }
+                }
+            }
+        }
+        tiles
+    }
+}
+
+// sort_back_to_front orders faces by the screen space depth of their centroids, furthest first.
+fn sort_back_to_front(world: &World, faces: &mut [wavefront::Face]) {
+    let depth = |f: &wavefront::Face| {
+        (0..3).map(|i| world.vp_p_mv.transform(f.vertices[i]).z).sum::<f32>()
+    };
+    // Larger depths are closer to the camera.
+    faces.sort_by(|a, b| depth(a).partial_cmp(&depth(b)).unwrap_or(Ordering::Equal));
+}
+
+// draw_face runs the shader's vertex stage on f, culls it, then rasterizes the pixels within
+// region, given as (x, y, w, h) in image coordinates, running the fragment stage on those that
+// pass the depth test and blending them into im.  Depth is only written when blend is
+// Blend::Replace.  im and z_buffer cover exactly region.
+fn draw_face<S: Shader, T: Target>(shdr: &mut S,
+                                   world: &World,
+                                   f: &wavefront::Face,
+                                   region: (usize, usize, usize, usize),
+                                   blend: Blend,
+                                   alpha_test: f32,
+                                   im: &mut T,
+                                   z_buffer: &mut draw::DepthBuffer) {
+    let tri = shdr.vertex(world, f);
+    if world.culled(&tri) {
+        return;
+    }
+    if let Some((dbc_dx, dbc_dy)) = raster::derivatives(&tri) {
+        shdr.derivatives(dbc_dx, dbc_dy);
+    }
+    let (x_off, y_off) = (region.0, region.1);
+    raster::rasterize_region(&tri, region, |x, y, bc: math::Vec3f| {
+        let (sx, sy) = (x - x_off, y - y_off);
+        let z = tri[0].z * bc.x + tri[1].z * bc.y + tri[2].z * bc.z;
+        // Z test passes, draw pixel
+        // Fragment may say to discard, then don't update z-buffer.
+        if z_buffer.get(sx, sy) < z && im.shade(&*shdr, world, sx, sy, bc, blend, alpha_test) &&
+           blend == Blend::Replace {
+            z_buffer.set(sx, sy, z);
+        }
+    });
+}
+
+// depth_tested_line draws the screen space segment a-b into im, skipping pixels where it is more
+// than bias behind z_buffer.
+fn depth_tested_line(a: math::Vec3f,
+                     b: math::Vec3f,
+                     c: draw::RGB,
+                     bias: f32,
+                     im: &mut draw::Image,
+                     z_buffer: &draw::DepthBuffer) {
+    let d = b - a;
+    let steps = d.x.abs().max(d.y.abs()).ceil().max(1.) as usize;
+    for i in 0..steps + 1 {
+        let p = a + d.scale(i as f32 / steps as f32);
+        if p.x < 0. || p.y < 0. {
+            continue;
+        }
+        let (x, y) = (p.x as usize, p.y as usize);
+        if x >= im.w || y >= im.h {
+            continue;
+        }
+        // Larger depths are closer to the camera.
+        if p.z + bias >= z_buffer.get(x, y) {
+            im.set(x, y, c);
+        }
+    }
+}
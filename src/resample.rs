@@ -0,0 +1,140 @@
+use std::f32::consts::PI;
+
+use color;
+use draw;
+
+// Reconstruction filter used by downsample, weighting source pixels by their distance, in output
+// pixels, from the output pixel's center.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Filter {
+    // Equal weight for the source pixels under the output pixel.  Fast, slightly aliased.
+    Box,
+    // Weight falling linearly to zero one output pixel away.  Smooth, slightly blurry.
+    Tent,
+    // Windowed sinc with 3 lobes.  Sharpest, can ring around hard edges.
+    Lanczos,
+    // Mitchell-Netravali cubic with B = C = 1/3, a compromise between blur and ringing.
+    Mitchell,
+}
+
+impl Filter {
+    // Distance, in output pixels, beyond which the weight is zero.
+    fn radius(&self) -> f32 {
+        match *self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.,
+            Filter::Lanczos => 3.,
+            Filter::Mitchell => 2.,
+        }
+    }
+
+    // weight returns the filter's unnormalized weight for a source pixel x output pixels from the
+    // output pixel's center.
+    pub fn weight(&self, x: f32) -> f32 {
+        let x = x.abs();
+        if x >= self.radius() {
+            return 0.;
+        }
+        match *self {
+            Filter::Box => 1.,
+            Filter::Tent => 1. - x,
+            Filter::Lanczos => {
+                if x < 1e-6 {
+                    return 1.;
+                }
+                let a = self.radius();
+                let px = PI * x;
+                a * px.sin() * (px / a).sin() / (px * px)
+            }
+            Filter::Mitchell => {
+                let (b, c) = (1. / 3., 1. / 3.);
+                let v = if x < 1. {
+                    (12. - 9. * b - 6. * c) * x * x * x + (-18. + 12. * b + 6. * c) * x * x +
+                    (6. - 2. * b)
+                } else {
+                    (-b - 6. * c) * x * x * x + (6. * b + 30. * c) * x * x +
+                    (-12. * b - 48. * c) * x + (8. * b + 24. * c)
+                };
+                v / 6.
+            }
+        }
+    }
+}
+
+// Weights of the source pixels contributing to one output pixel along one axis.
+struct Taps {
+    first: usize,
+    weights: Vec<f32>,
+}
+
+// taps returns, for each of the n / factor output pixels along an axis n source pixels long, the
+// normalized filter weights of its source pixels.  Taps past the edges are dropped.
+fn taps(n: usize, factor: usize, filter: Filter) -> Vec<Taps> {
+    let f = factor as f32;
+    let reach = filter.radius() * f;
+    (0..n / factor)
+        .map(|o| {
+            let center = (o as f32 + 0.5) * f;
+            let first = (center - reach).floor().max(0.) as usize;
+            let last = ((center + reach).ceil() as usize).min(n);
+            let mut weights: Vec<f32> = (first..last)
+                .map(|s| filter.weight((s as f32 + 0.5 - center) / f))
+                .collect();
+            let sum: f32 = weights.iter().sum();
+            if sum != 0. {
+                for w in &mut weights {
+                    *w /= sum;
+                }
+            }
+            Taps {
+                first: first,
+                weights: weights,
+            }
+        })
+        .collect()
+}
+
+// downsample shrinks src by factor in each direction, filtering with filter in linear space.
+// Render at factor times the output size, with the viewport set to match, and downsample the
+// result for a supersampled image.  Any remainder of src's size not filling a whole output pixel
+// is dropped.
+pub fn downsample(src: &draw::Image, factor: usize, filter: Filter) -> draw::Image {
+    assert!(factor > 0, "factor must be at least 1");
+    let (w, h) = (src.w / factor, src.h / factor);
+    let x_taps = taps(src.w, factor, filter);
+    let y_taps = taps(src.h, factor, filter);
+
+    let linear: Vec<f32> = src.buf.iter().map(|&c| color::srgb_to_linear(c)).collect();
+    // Filter rows first into a w x src.h intermediate.
+    let mut rows = vec![0.; w * src.h * 3];
+    for y in 0..src.h {
+        for (x, t) in x_taps.iter().enumerate() {
+            for (i, wt) in t.weights.iter().enumerate() {
+                let off = ((t.first + i) + y * src.w) * 3;
+                for c in 0..3 {
+                    rows[(x + y * w) * 3 + c] += linear[off + c] * wt;
+                }
+            }
+        }
+    }
+    let mut im = draw::Image::new(w, h);
+    for (y, t) in y_taps.iter().enumerate() {
+        for x in 0..w {
+            let mut sum = [0.; 3];
+            for (i, wt) in t.weights.iter().enumerate() {
+                let off = (x + (t.first + i) * w) * 3;
+                for c in 0..3 {
+                    sum[c] += rows[off + c] * wt;
+                }
+            }
+            im.set(x,
+                   y,
+                   draw::RGB {
+                       r: color::linear_to_srgb(sum[0]),
+                       g: color::linear_to_srgb(sum[1]),
+                       b: color::linear_to_srgb(sum[2]),
+                   });
+        }
+    }
+    im
+}
@@ -1,23 +1,54 @@
+use std::f32::consts::PI;
+
+use color;
 use draw;
+use env;
+use hdr;
+use light;
 use math;
+use shadow;
 use wavefront;
 
-static DEPTH_RESOLUTION: f32 = 65_536.;
+// Range of depth values the viewport maps normalized device z onto.
+pub static DEPTH_RESOLUTION: f32 = 65_536.;
 
-fn bbox(tri: &[math::Vec3f; 3]) -> (i32, i32, i32, i32) {
-    let ref v0 = tri[0].to_vec2i();
-    let ref v1 = tri[1].to_vec2i();
-    let ref v2 = tri[2].to_vec2i();
+// Winding order of a triangle's vertices as it appears on screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Winding {
+    // Clockwise.
+    CW,
+    // Counter-clockwise.
+    CCW,
+}
 
-    use std::cmp::{max, min};
-    let (x_min, x_max) = (min(min(v0.x, v1.x), v2.x), max(max(v0.x, v1.x), v2.x));
-    let (y_min, y_max) = (min(min(v0.y, v1.y), v2.y), max(max(v0.y, v1.y), v2.y));
-    debug!("Tri BBox x {},{} y {},{}", x_min, x_max, y_min, y_max);
-    (x_min, x_max, y_min, y_max)
+// Which faces, if any, are discarded before rasterization.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CullMode {
+    None,
+    Back,
+    Front,
+}
+
+// Returns the winding of the screen space triangle tri, or None if it is degenerate. Screen space
+// has y pointing up, the image is flipped before writing.
+fn winding(tri: &[math::Vec3f; 3]) -> Option<Winding> {
+    let area = (tri[1].x - tri[0].x) * (tri[2].y - tri[0].y) -
+               (tri[1].y - tri[0].y) * (tri[2].x - tri[0].x);
+    if area > 0. {
+        Some(Winding::CCW)
+    } else if area < 0. {
+        Some(Winding::CW)
+    } else {
+        None
+    }
 }
 
 pub struct World {
-    light_dir: math::Vec3f,
+    lights: Vec<light::Light>,
+    cull_mode: CullMode,
+    front_face: Winding,
+    // Camera position in model space.
+    eye: math::Vec3f,
     pub model_view: math::Matrix,
     pub viewport: math::Matrix,
     pub projection: math::Matrix,
@@ -28,12 +59,21 @@ pub struct World {
 }
 
 impl World {
+    // new returns a world lit by a single white directional light shining from -z, call
+    // clear_lights to light it differently.
     pub fn new() -> Self {
         World {
-            light_dir: math::Vec3f {
+            lights: vec![light::Light::directional(math::Vec3f {
+                             x: 0.,
+                             y: 0.,
+                             z: -1.,
+                         })],
+            cull_mode: CullMode::Back,
+            front_face: Winding::CCW,
+            eye: math::Vec3f {
                 x: 0.,
                 y: 0.,
-                z: -1.,
+                z: 1.,
             },
             model_view: math::Matrix::identity(),
             viewport: math::Matrix::identity(),
@@ -44,8 +84,41 @@ impl World {
         }
     }
 
-    pub fn set_light_dir(&mut self, light_dir: math::Vec3f) {
-        self.light_dir = light_dir;
+    // add_light adds light to the scene and returns its index in lights().
+    pub fn add_light(&mut self, light: light::Light) -> usize {
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
+
+    pub fn clear_lights(&mut self) {
+        self.lights.clear();
+    }
+
+    pub fn lights(&self) -> &[light::Light] {
+        &self.lights
+    }
+
+    pub fn set_cull_mode(&mut self, cull_mode: CullMode) {
+        self.cull_mode = cull_mode;
+    }
+
+    // Sets the winding, in screen space, of faces considered front facing.
+    pub fn set_front_face(&mut self, front_face: Winding) {
+        self.front_face = front_face;
+    }
+
+    // culled returns true if the screen space triangle tri should not be rasterized under the
+    // current cull mode. Degenerate triangles cover no pixels and are always culled.
+    pub fn culled(&self, tri: &[math::Vec3f; 3]) -> bool {
+        let front = match winding(tri) {
+            Some(w) => w == self.front_face,
+            None => return true,
+        };
+        match self.cull_mode {
+            CullMode::None => false,
+            CullMode::Back => !front,
+            CullMode::Front => front,
+        }
     }
 
     fn rebuild_matrices(&mut self) {
@@ -66,11 +139,24 @@ impl World {
             m_inv[(2, i)] = z[i];
             t_r[(i, 3)] = -center[i];
         }
+        self.eye = eye;
         self.model_view = m_inv * t_r;
         self.projection[(3, 2)] = -1. / (eye - center).length();
         self.rebuild_matrices()
     }
 
+    // Returns the camera position in model space, as given to look_at.
+    pub fn eye(&self) -> math::Vec3f {
+        self.eye
+    }
+
+    // orthographic replaces the perspective projection set up by look_at with a parallel one, as
+    // used when rendering from a directional light.
+    pub fn orthographic(&mut self) {
+        self.projection = math::Matrix::identity();
+        self.rebuild_matrices()
+    }
+
     pub fn set_viewport(&mut self, x_off: usize, y_off: usize, width: usize, height: usize) {
         let x = x_off as f32;
         let y = y_off as f32;
@@ -89,42 +175,73 @@ impl World {
     }
 }
 
+// Shader implementations are driven by render::Renderer, which calls vertex on each face, culls it
+// according to world's cull mode, and then calls fragment per-pixel.  Shaders are cloned so faces
+// can be shaded on several threads at once.
 pub trait Shader {
-    // vertex sets per-face shader state in preparation for fragment evaluation.
+    // vertex sets per-face shader state in preparation for fragment evaluation, and returns the
+    // screen space vertices of the face.
     fn vertex(&mut self, world: &World, f: &wavefront::Face) -> [math::Vec3f; 3];
     // fragment evaluates the color of a pixel fragment. It returns None if the pixel should be
-    // discarded, i.e. culled as a back facing polygon.
+    // discarded.
     fn fragment(&self, world: &World, bc: math::Vec3f) -> Option<draw::RGB>;
-    // draw_face calls vertex on the face, and then fragment per-pixel.
-    fn draw_face(&mut self, world: &World, f: &wavefront::Face);
+    // derivatives is called after vertex for faces that weren't culled, with the change in the
+    // barycentric coordinates passed to fragment one pixel right and one pixel down on screen.
+    // Shaders filtering textures use it to find how quickly texture coordinates change.
+    fn derivatives(&mut self, _dbc_dx: math::Vec3f, _dbc_dy: math::Vec3f) {}
+    // fragment_linear evaluates the linear color of a pixel fragment, with its opacity, 0
+    // transparent to 1 opaque, in a.  render::Renderer uses it for an hdr::HdrImage and wherever it
+    // needs alpha to test or blend with.  By default it decodes fragment's sRGB, opaque.  Shaders
+    // lighting in linear space override it to return light above 1 unclamped.
+    fn fragment_linear(&self, world: &World, bc: math::Vec3f) -> Option<hdr::Rgba> {
+        self.fragment(world, bc).map(hdr::Rgba::from_srgb)
+    }
+}
+
+// lit decodes the sRGB texel c to linear and scales it by light, per channel red, green and blue
+// in x, y and z, keeping c's alpha.  Light above 1 is kept for tone mapping.
+fn lit(c: draw::RGBA, light: math::Vec3f) -> hdr::Rgba {
+    hdr::Rgba {
+        r: color::srgb_to_linear(c.r) * light.x,
+        g: color::srgb_to_linear(c.g) * light.y,
+        b: color::srgb_to_linear(c.b) * light.z,
+        a: c.a as f32 / 255.,
+    }
+}
+
+// Interpolates per-vertex values v at barycentric coordinates bc.
+fn interpolate(v: &[math::Vec3f; 3], bc: math::Vec3f) -> math::Vec3f {
+    v[0].scale(bc.x) + v[1].scale(bc.y) + v[2].scale(bc.z)
 }
 
+// Interpolates per-vertex scalars v at barycentric coordinates bc.
+fn interpolate_f32(v: &[f32; 3], bc: math::Vec3f) -> f32 {
+    v[0] * bc.x + v[1] * bc.y + v[2] * bc.z
+}
+
+#[derive(Clone)]
 pub struct FlatShader<'a> {
     // Uniform values.
     obj: &'a wavefront::Object,
-    im: &'a mut draw::Image,
-    z_buffer: &'a mut draw::DepthBuffer,
 
     // Varying values, written by vertex shader, read by fragment shader
-    // Per-face lighting scalar.
-    intensity: f32,
+    // Per-face light, red, green and blue in x, y and z.
+    light: math::Vec3f,
     // Texture UV at fragment.
     uvs: [math::Vec3f; 3],
+    // Change in texture UV one pixel right and one pixel down on screen.
+    duv: [math::Vec3f; 2],
     // Normal UV at fragment.
     ns: [math::Vec3f; 3],
 }
 
 impl<'a> FlatShader<'a> {
-    pub fn new(obj: &'a wavefront::Object,
-               im: &'a mut draw::Image,
-               z_buffer: &'a mut draw::DepthBuffer)
-               -> Self {
+    pub fn new(obj: &'a wavefront::Object) -> Self {
         FlatShader {
             obj: obj,
-            im: im,
-            z_buffer: z_buffer,
-            intensity: 1.,
+            light: math::Vec3f::zero(),
             uvs: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+            duv: [math::Vec3f::zero(), math::Vec3f::zero()],
             ns: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
         }
     }
@@ -134,88 +251,76 @@ impl<'a> Shader for FlatShader<'a> {
     fn vertex(&mut self, world: &World, f: &wavefront::Face) -> [math::Vec3f; 3] {
         // screen space vertices of the face.
         let mut screen_verts = [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()];
-        self.intensity = 0.;
         for i in 0..3 {
-            self.intensity += math::dot(f.normals[i], world.light_dir.normalize()) / 3.;
             self.ns[i] = f.normals[i];
             self.uvs[i] = f.texcoords[i];
             screen_verts[i] = world.vp_p_mv.transform(f.vertices[i]);
         }
+        // Light the whole face as seen from its centroid.
+        let center = interpolate(&f.vertices,
+                                 math::Vec3f {
+                                     x: 1. / 3.,
+                                     y: 1. / 3.,
+                                     z: 1. / 3.,
+                                 });
+        self.light = math::Vec3f::zero();
+        for l in world.lights() {
+            if let Some(inc) = l.incident(center) {
+                let mut intensity = 0.;
+                for i in 0..3 {
+                    intensity += math::dot(f.normals[i], inc.dir) / 3.;
+                }
+                if intensity > 0. {
+                    self.light = self.light + inc.radiance.scale(intensity);
+                }
+            }
+        }
+        self.light = self.light.scale((f.ao[0] + f.ao[1] + f.ao[2]) / 3.);
         screen_verts
     }
 
-    fn fragment(&self, _world: &World, bc: math::Vec3f) -> Option<draw::RGB> {
-        if self.intensity < 0. {
-            return None;
-        }
-        let uv = self.uvs[0].scale(bc.x) + self.uvs[1].scale(bc.y) + self.uvs[2].scale(bc.z);
-        let c = self.obj.diffuse_sample(uv);
-        Some(draw::RGB {
-            r: (c.r as f32 * self.intensity) as u8,
-            g: (c.g as f32 * self.intensity) as u8,
-            b: (c.b as f32 * self.intensity) as u8,
-        })
+    fn derivatives(&mut self, dbc_dx: math::Vec3f, dbc_dy: math::Vec3f) {
+        self.duv = [interpolate(&self.uvs, dbc_dx), interpolate(&self.uvs, dbc_dy)];
     }
 
-    fn draw_face(&mut self, world: &World, f: &wavefront::Face) {
-        // Screen space triangle.
-        let ref tri = self.vertex(world, f);
-        let (x_min, x_max, y_min, y_max) = bbox(tri);
-        for y in y_min..y_max + 1 {
-            for x in x_min..x_max + 1 {
-                let bc = math::barycentric(tri,
-                                           math::Vec3f {
-                                               x: x as f32,
-                                               y: y as f32,
-                                               z: 0.,
-                                           });
-                if bc.x < 0. || bc.y < 0. || bc.z < 0. {
-                    // Outside the triangle.
-                    continue;
-                }
+    fn fragment(&self, world: &World, bc: math::Vec3f) -> Option<draw::RGB> {
+        self.fragment_linear(world, bc).map(|c| c.to_srgb())
+    }
 
-                let (sx, sy) = (x as usize, y as usize);
-                let z = tri[0].z * bc.x + tri[1].z * bc.y + tri[2].z * bc.z;
-                // Z test passes, draw pixel
-                if self.z_buffer.get(sx, sy) < z {
-                    match self.fragment(world, bc) {
-                        Some(c) => {
-                            self.z_buffer.set(sx, sy, z);
-                            self.im.set(sx, sy, c);
-                        }
-                        // Fragment says to discard, don't update z-buffer.
-                        None => {}
-                    }
-                }
-            }
-        }
+    fn fragment_linear(&self, world: &World, bc: math::Vec3f) -> Option<hdr::Rgba> {
+        let uv = interpolate(&self.uvs, bc);
+        let c = self.obj.diffuse_rgba_grad(uv, self.duv[0], self.duv[1]);
+        Some(lit(c, self.light))
     }
 }
 
+#[derive(Clone)]
 pub struct GouraudShader<'a> {
     // Uniform values.
     obj: &'a wavefront::Object,
-    im: &'a mut draw::Image,
-    z_buffer: &'a mut draw::DepthBuffer,
 
     // Varying values, written by vertex shader, read by fragment shader
     // Texture UV at fragment.
     uvs: [math::Vec3f; 3],
+    // Change in texture UV one pixel right and one pixel down on screen.
+    duv: [math::Vec3f; 2],
     // Normal UV at fragment.
     ns: [math::Vec3f; 3],
+    // Model space position at fragment.
+    verts: [math::Vec3f; 3],
+    // Baked ambient occlusion at fragment.
+    ao: [f32; 3],
 }
 
 impl<'a> GouraudShader<'a> {
-    pub fn new(obj: &'a wavefront::Object,
-               im: &'a mut draw::Image,
-               z_buffer: &'a mut draw::DepthBuffer)
-               -> Self {
+    pub fn new(obj: &'a wavefront::Object) -> Self {
         GouraudShader {
             obj: obj,
-            im: im,
-            z_buffer: z_buffer,
             uvs: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+            duv: [math::Vec3f::zero(), math::Vec3f::zero()],
             ns: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+            verts: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+            ao: [1., 1., 1.],
         }
     }
 }
@@ -227,60 +332,516 @@ impl<'a> Shader for GouraudShader<'a> {
         for i in 0..3 {
             self.ns[i] = f.normals[i];
             self.uvs[i] = f.texcoords[i];
+            self.verts[i] = f.vertices[i];
+            self.ao[i] = f.ao[i];
             screen_verts[i] = world.vp_p_mv.transform(f.vertices[i]);
         }
         screen_verts
     }
 
+    fn derivatives(&mut self, dbc_dx: math::Vec3f, dbc_dy: math::Vec3f) {
+        self.duv = [interpolate(&self.uvs, dbc_dx), interpolate(&self.uvs, dbc_dy)];
+    }
+
     fn fragment(&self, world: &World, bc: math::Vec3f) -> Option<draw::RGB> {
-        let uv = self.uvs[0].scale(bc.x) + self.uvs[1].scale(bc.y) + self.uvs[2].scale(bc.z);
-        let c = self.obj.diffuse_sample(uv);
-        let n = self.obj.normal_sample(uv);
+        self.fragment_linear(world, bc).map(|c| c.to_srgb())
+    }
+
+    fn fragment_linear(&self, world: &World, bc: math::Vec3f) -> Option<hdr::Rgba> {
+        let uv = interpolate(&self.uvs, bc);
+        let p = interpolate(&self.verts, bc);
+        let c = self.obj.diffuse_rgba_grad(uv, self.duv[0], self.duv[1]);
+        let n = self.obj.normal_sample_grad(uv, self.duv[0], self.duv[1]);
         let n = world.mit.transform(n).normalize();
-        let l = world.m.transform(world.light_dir).normalize();
-        let mut intensity = math::dot(n, l);
-        if intensity < 0. {
-            intensity = 0.;
+        let mut light = math::Vec3f::zero();
+        for lt in world.lights() {
+            if let Some(inc) = lt.incident(p) {
+                let l = world.m.transform(inc.dir).normalize();
+                let intensity = math::dot(n, l);
+                if intensity > 0. {
+                    light = light + inc.radiance.scale(intensity);
+                }
+            }
         }
-        Some(draw::RGB {
-            r: (c.r as f32 * intensity) as u8,
-            g: (c.g as f32 * intensity) as u8,
-            b: (c.b as f32 * intensity) as u8,
-        })
+        Some(lit(c, light.scale(interpolate_f32(&self.ao, bc))))
+    }
+}
+
+// Which specular model PhongShader evaluates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Specular {
+    // Reflected light vector against the view vector.
+    Phong,
+    // Half vector between light and view against the normal.
+    BlinnPhong,
+}
 
+#[derive(Clone)]
+pub struct PhongShader<'a> {
+    // Uniform values.
+    obj: &'a wavefront::Object,
+    specular_model: Specular,
+    // Weights of the ambient, diffuse and specular terms.
+    ambient: f32,
+    diffuse: f32,
+    specular: f32,
+    // Specular exponent, higher for smaller, sharper highlights, used where the object has no
+    // specular map.
+    shininess: f32,
+    shadow_map: Option<&'a shadow::ShadowMap>,
+
+    // Varying values, written by vertex shader, read by fragment shader
+    // Texture UV at fragment.
+    uvs: [math::Vec3f; 3],
+    // Change in texture UV one pixel right and one pixel down on screen.
+    duv: [math::Vec3f; 2],
+    // Model space position at fragment.
+    verts: [math::Vec3f; 3],
+    // Baked ambient occlusion at fragment.
+    ao: [f32; 3],
+}
+
+impl<'a> PhongShader<'a> {
+    pub fn new(obj: &'a wavefront::Object) -> Self {
+        PhongShader {
+            obj: obj,
+            specular_model: Specular::BlinnPhong,
+            ambient: 0.02,
+            diffuse: 1.,
+            specular: 0.6,
+            shininess: 32.,
+            shadow_map: None,
+            uvs: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+            duv: [math::Vec3f::zero(), math::Vec3f::zero()],
+            verts: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+            ao: [1., 1., 1.],
+        }
     }
 
-    fn draw_face(&mut self, world: &World, f: &wavefront::Face) {
-        // Screen space triangle.
-        let ref tri = self.vertex(world, f);
-        let (x_min, x_max, y_min, y_max) = bbox(tri);
-        for y in y_min..y_max + 1 {
-            for x in x_min..x_max + 1 {
-                let bc = math::barycentric(tri,
-                                           math::Vec3f {
-                                               x: x as f32,
-                                               y: y as f32,
-                                               z: 0.,
-                                           });
-                if bc.x < 0. || bc.y < 0. || bc.z < 0. {
-                    // Outside the triangle.
-                    continue;
+    pub fn set_specular_model(&mut self, specular_model: Specular) {
+        self.specular_model = specular_model;
+    }
+
+    // Sets the weights of the ambient, diffuse and specular terms.
+    pub fn set_coefficients(&mut self, ambient: f32, diffuse: f32, specular: f32) {
+        self.ambient = ambient;
+        self.diffuse = diffuse;
+        self.specular = specular;
+    }
+
+    // Sets the specular exponent used where the object has no specular map.
+    pub fn set_shininess(&mut self, shininess: f32) {
+        self.shininess = shininess;
+    }
+
+    // Shadows the diffuse and specular terms of the light shadow_map was rendered from.
+    pub fn set_shadow_map(&mut self, shadow_map: &'a shadow::ShadowMap) {
+        self.shadow_map = Some(shadow_map);
+    }
+}
+
+impl<'a> Shader for PhongShader<'a> {
+    fn vertex(&mut self, world: &World, f: &wavefront::Face) -> [math::Vec3f; 3] {
+        // screen space vertices of the face.
+        let mut screen_verts = [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()];
+        for i in 0..3 {
+            self.uvs[i] = f.texcoords[i];
+            self.verts[i] = f.vertices[i];
+            self.ao[i] = f.ao[i];
+            screen_verts[i] = world.vp_p_mv.transform(f.vertices[i]);
+        }
+        screen_verts
+    }
+
+    fn derivatives(&mut self, dbc_dx: math::Vec3f, dbc_dy: math::Vec3f) {
+        self.duv = [interpolate(&self.uvs, dbc_dx), interpolate(&self.uvs, dbc_dy)];
+    }
+
+    fn fragment(&self, world: &World, bc: math::Vec3f) -> Option<draw::RGB> {
+        self.fragment_linear(world, bc).map(|c| c.to_srgb())
+    }
+
+    fn fragment_linear(&self, world: &World, bc: math::Vec3f) -> Option<hdr::Rgba> {
+        let uv = interpolate(&self.uvs, bc);
+        let p = interpolate(&self.verts, bc);
+        let c = self.obj.diffuse_rgba_grad(uv, self.duv[0], self.duv[1]);
+        let n = self.obj.normal_sample_grad(uv, self.duv[0], self.duv[1]);
+        let n = world.mit.transform(n).normalize();
+        // The camera looks down -z after the model view and projection transforms.
+        let v = math::Vec3f {
+            x: 0.,
+            y: 0.,
+            z: 1.,
+        };
+        // The specular map gives the shininess of each texel.
+        let shininess = self.obj.specular_sample(uv).unwrap_or(self.shininess);
+
+        let mut light = math::Vec3f {
+            x: self.ambient,
+            y: self.ambient,
+            z: self.ambient,
+        };
+        for (i, lt) in world.lights().iter().enumerate() {
+            let inc = match lt.incident(p) {
+                Some(inc) => inc,
+                None => continue,
+            };
+            let l = world.m.transform(inc.dir).normalize();
+            let diff = math::dot(n, l);
+            if diff <= 0. {
+                continue;
+            }
+            let cos = match self.specular_model {
+                Specular::Phong => {
+                    // l reflected about n.
+                    let r = (n.scale(2. * diff) - l).normalize();
+                    math::dot(r, v)
                 }
+                Specular::BlinnPhong => math::dot(n, (l + v).normalize()),
+            };
+            let spec = cos.max(0.).powf(shininess);
+            let lit = match self.shadow_map {
+                Some(sm) if sm.light() == i => sm.visibility(p),
+                _ => 1.,
+            };
+            light = light + inc.radiance.scale(lit * (self.diffuse * diff + self.specular * spec));
+        }
+        Some(lit(c, light.scale(interpolate_f32(&self.ao, bc))))
+    }
+}
+
+// ToonShader is a cel shader, the diffuse lighting from each light is quantized into a few flat
+// bands.  Pair it with post::Outline for an illustrated look.
+#[derive(Clone)]
+pub struct ToonShader<'a> {
+    // Uniform values.
+    obj: &'a wavefront::Object,
+    bands: usize,
+    // Light applied everywhere, including the darkest band.
+    ambient: f32,
 
-                let (sx, sy) = (x as usize, y as usize);
-                let z = tri[0].z * bc.x + tri[1].z * bc.y + tri[2].z * bc.z;
-                // Z test passes, draw pixel
-                if self.z_buffer.get(sx, sy) < z {
-                    match self.fragment(world, bc) {
-                        Some(c) => {
-                            self.z_buffer.set(sx, sy, z);
-                            self.im.set(sx, sy, c);
-                        }
-                        // Fragment says to discard, don't update z-buffer.
-                        None => {}
-                    }
+    // Varying values, written by vertex shader, read by fragment shader
+    // Texture UV at fragment.
+    uvs: [math::Vec3f; 3],
+    // Change in texture UV one pixel right and one pixel down on screen.
+    duv: [math::Vec3f; 2],
+    // Vertex normal at fragment.
+    ns: [math::Vec3f; 3],
+    // Model space position at fragment.
+    verts: [math::Vec3f; 3],
+}
+
+impl<'a> ToonShader<'a> {
+    pub fn new(obj: &'a wavefront::Object) -> Self {
+        ToonShader {
+            obj: obj,
+            bands: 4,
+            ambient: 0.1,
+            uvs: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+            duv: [math::Vec3f::zero(), math::Vec3f::zero()],
+            ns: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+            verts: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+        }
+    }
+
+    // Sets the number of lit bands dot(n, l) is quantized into.
+    pub fn set_bands(&mut self, bands: usize) {
+        assert!(bands > 0, "need at least one band");
+        self.bands = bands;
+    }
+
+    pub fn set_ambient(&mut self, ambient: f32) {
+        self.ambient = ambient;
+    }
+}
+
+impl<'a> Shader for ToonShader<'a> {
+    fn vertex(&mut self, world: &World, f: &wavefront::Face) -> [math::Vec3f; 3] {
+        // screen space vertices of the face.
+        let mut screen_verts = [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()];
+        for i in 0..3 {
+            self.ns[i] = f.normals[i];
+            self.uvs[i] = f.texcoords[i];
+            self.verts[i] = f.vertices[i];
+            screen_verts[i] = world.vp_p_mv.transform(f.vertices[i]);
+        }
+        screen_verts
+    }
+
+    fn derivatives(&mut self, dbc_dx: math::Vec3f, dbc_dy: math::Vec3f) {
+        self.duv = [interpolate(&self.uvs, dbc_dx), interpolate(&self.uvs, dbc_dy)];
+    }
+
+    fn fragment(&self, world: &World, bc: math::Vec3f) -> Option<draw::RGB> {
+        self.fragment_linear(world, bc).map(|c| c.to_srgb())
+    }
+
+    fn fragment_linear(&self, world: &World, bc: math::Vec3f) -> Option<hdr::Rgba> {
+        let uv = interpolate(&self.uvs, bc);
+        let p = interpolate(&self.verts, bc);
+        let c = self.obj.diffuse_rgba_grad(uv, self.duv[0], self.duv[1]);
+        let n = world.mit.transform(interpolate(&self.ns, bc)).normalize();
+        let bands = self.bands as f32;
+        let mut light = math::Vec3f {
+            x: self.ambient,
+            y: self.ambient,
+            z: self.ambient,
+        };
+        for lt in world.lights() {
+            if let Some(inc) = lt.incident(p) {
+                let l = world.m.transform(inc.dir).normalize();
+                let intensity = math::dot(n, l);
+                if intensity > 0. {
+                    // Round up so any lit fragment lands in at least the first band.
+                    light = light + inc.radiance.scale((intensity * bands).ceil() / bands);
                 }
             }
         }
+        Some(lit(c, light))
+    }
+}
+
+// NormalShader colors each fragment with its interpolated vertex normal, after the model view and
+// projection transforms, mapping each component from [-1, 1] to [0, 255].  The result feeds
+// post::Outline and is useful for inspecting a model's normals.
+#[derive(Clone)]
+pub struct NormalShader {
+    // Varying values, written by vertex shader, read by fragment shader
+    // Vertex normal at fragment.
+    ns: [math::Vec3f; 3],
+}
+
+impl NormalShader {
+    pub fn new() -> Self {
+        NormalShader { ns: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()] }
+    }
+}
+
+impl Shader for NormalShader {
+    fn vertex(&mut self, world: &World, f: &wavefront::Face) -> [math::Vec3f; 3] {
+        // screen space vertices of the face.
+        let mut screen_verts = [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()];
+        for i in 0..3 {
+            self.ns[i] = f.normals[i];
+            screen_verts[i] = world.vp_p_mv.transform(f.vertices[i]);
+        }
+        screen_verts
+    }
+
+    fn fragment(&self, world: &World, bc: math::Vec3f) -> Option<draw::RGB> {
+        let n = world.mit.transform(interpolate(&self.ns, bc)).normalize();
+        Some(draw::RGB {
+            r: ((n.x + 1.) * 127.5) as u8,
+            g: ((n.y + 1.) * 127.5) as u8,
+            b: ((n.z + 1.) * 127.5) as u8,
+        })
+    }
+}
+
+// Multiplies a and b component-wise.
+fn mul(a: math::Vec3f, b: math::Vec3f) -> math::Vec3f {
+    math::Vec3f {
+        x: a.x * b.x,
+        y: a.y * b.y,
+        z: a.z * b.z,
+    }
+}
+
+// Smallest roughness PbrShader shades with.  At 0 the GGX distribution is 0/0 where the normal
+// meets the half vector.
+const MIN_ROUGHNESS: f32 = 0.045;
+
+// PbrShader is a physically based shader using the metallic/roughness model.  Specular is the
+// Cook-Torrance microfacet BRDF with the GGX distribution, Smith-Schlick geometry term and Schlick
+// Fresnel, diffuse is Lambertian scaled by the energy not reflected specularly.  Textures are
+// decoded from sRGB and lighting is done in linear space, then encoded back to sRGB.
+//
+// Light radiance is scaled by pi so a white light of intensity 1 on a white diffuse surface facing
+// it is white, as with the other shaders.
+#[derive(Clone)]
+pub struct PbrShader<'a> {
+    // Uniform values.
+    obj: &'a wavefront::Object,
+    // Used where the object has no metallic or roughness map.
+    metallic: f32,
+    roughness: f32,
+    // Linear light reaching every fragment from all directions.
+    ambient: f32,
+    // Replaces ambient with image based lighting when set.
+    environment: Option<&'a env::EnvironmentMap>,
+
+    // Varying values, written by vertex shader, read by fragment shader
+    // Texture UV at fragment.
+    uvs: [math::Vec3f; 3],
+    // Change in texture UV one pixel right and one pixel down on screen.
+    duv: [math::Vec3f; 2],
+    // Model space position at fragment.
+    verts: [math::Vec3f; 3],
+    // Baked ambient occlusion at fragment.
+    ao: [f32; 3],
+}
+
+impl<'a> PbrShader<'a> {
+    pub fn new(obj: &'a wavefront::Object) -> Self {
+        PbrShader {
+            obj: obj,
+            metallic: 0.,
+            roughness: 0.5,
+            ambient: 0.03,
+            environment: None,
+            uvs: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+            duv: [math::Vec3f::zero(), math::Vec3f::zero()],
+            verts: [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()],
+            ao: [1., 1., 1.],
+        }
+    }
+
+    // Sets the metalness, 0 to 1, used when the object has no metallic map.
+    pub fn set_metallic(&mut self, metallic: f32) {
+        self.metallic = metallic;
+    }
+
+    // Sets the roughness, 0 to 1, used when the object has no roughness map.
+    pub fn set_roughness(&mut self, roughness: f32) {
+        self.roughness = roughness;
+    }
+
+    pub fn set_ambient(&mut self, ambient: f32) {
+        self.ambient = ambient;
+    }
+
+    // Lights the object with environment_map, diffuse from its irradiance and specular from its
+    // prefiltered reflection at the fragment's roughness, instead of the constant ambient term.
+    pub fn set_environment(&mut self, environment_map: &'a env::EnvironmentMap) {
+        self.environment = Some(environment_map);
+    }
+}
+
+// env_brdf approximates the split sum environment BRDF integrated over the hemisphere, returning
+// the scale and bias applied to f0.  This is Karis' analytic fit for mobile.
+fn env_brdf(roughness: f32, n_dot_v: f32) -> (f32, f32) {
+    let r = (1. - roughness,
+             0.0425 - 0.0275 * roughness,
+             1.04 - 0.572 * roughness,
+             0.022 * roughness - 0.04);
+    let a004 = (r.0 * r.0).min((-9.28 * n_dot_v).exp2()) * r.0 + r.1;
+    (-1.04 * a004 + r.2, 1.04 * a004 + r.3)
+}
+
+impl<'a> Shader for PbrShader<'a> {
+    fn vertex(&mut self, world: &World, f: &wavefront::Face) -> [math::Vec3f; 3] {
+        // screen space vertices of the face.
+        let mut screen_verts = [math::Vec3f::zero(), math::Vec3f::zero(), math::Vec3f::zero()];
+        for i in 0..3 {
+            self.uvs[i] = f.texcoords[i];
+            self.verts[i] = f.vertices[i];
+            self.ao[i] = f.ao[i];
+            screen_verts[i] = world.vp_p_mv.transform(f.vertices[i]);
+        }
+        screen_verts
+    }
+
+    fn derivatives(&mut self, dbc_dx: math::Vec3f, dbc_dy: math::Vec3f) {
+        self.duv = [interpolate(&self.uvs, dbc_dx), interpolate(&self.uvs, dbc_dy)];
+    }
+
+    fn fragment(&self, world: &World, bc: math::Vec3f) -> Option<draw::RGB> {
+        self.fragment_linear(world, bc).map(|c| c.to_srgb())
+    }
+
+    fn fragment_linear(&self, world: &World, bc: math::Vec3f) -> Option<hdr::Rgba> {
+        Some(self.radiance(world, bc))
+    }
+}
+
+impl<'a> PbrShader<'a> {
+    // radiance returns the linear light leaving the fragment at bc towards the camera, with the
+    // diffuse map's alpha.
+    fn radiance(&self, world: &World, bc: math::Vec3f) -> hdr::Rgba {
+        let uv = interpolate(&self.uvs, bc);
+        let p = interpolate(&self.verts, bc);
+        let c = self.obj.diffuse_rgba_grad(uv, self.duv[0], self.duv[1]);
+        let albedo = math::Vec3f {
+            x: color::srgb_to_linear(c.r),
+            y: color::srgb_to_linear(c.g),
+            z: color::srgb_to_linear(c.b),
+        };
+        let metallic = self.obj.metallic_sample(uv).unwrap_or(self.metallic);
+        let roughness = self.obj.roughness_sample(uv).unwrap_or(self.roughness).max(MIN_ROUGHNESS);
+        let n = self.obj.normal_sample_grad(uv, self.duv[0], self.duv[1]);
+        let n = world.mit.transform(n).normalize();
+        // The camera looks down -z after the model view and projection transforms.
+        let v = math::Vec3f {
+            x: 0.,
+            y: 0.,
+            z: 1.,
+        };
+        let n_dot_v = math::dot(n, v).max(1e-4);
+
+        // Reflectance at normal incidence, 4% for dielectrics, the albedo for metals.
+        let dielectric = math::Vec3f {
+            x: 0.04,
+            y: 0.04,
+            z: 0.04,
+        };
+        let f0 = dielectric.scale(1. - metallic) + albedo.scale(metallic);
+        let one = math::Vec3f {
+            x: 1.,
+            y: 1.,
+            z: 1.,
+        };
+        let a = roughness * roughness;
+        let a2 = a * a;
+        // Smith-Schlick k for direct lighting.
+        let k = (roughness + 1.) * (roughness + 1.) / 8.;
+        let g_v = n_dot_v / (n_dot_v * (1. - k) + k);
+
+        let mut radiance = match self.environment {
+            None => albedo.scale(self.ambient),
+            Some(em) => {
+                // The environment is looked up in model space, where the map's directions are.
+                let n = self.obj.normal_sample_grad(uv, self.duv[0], self.duv[1]).normalize();
+                let v = (world.eye() - p).normalize();
+                let n_dot_v = math::dot(n, v).max(1e-4);
+                let (scale, bias) = env_brdf(roughness, n_dot_v);
+                let fresnel = f0.scale(scale) + one.scale(bias);
+                let kd = (one - fresnel).scale(1. - metallic);
+                let diffuse = mul(mul(kd, albedo), em.irradiance(n));
+                let r = env::reflect(v.scale(-1.), n);
+                diffuse + mul(fresnel, em.sample_glossy(r, roughness))
+            }
+        };
+        for lt in world.lights() {
+            let inc = match lt.incident(p) {
+                Some(inc) => inc,
+                None => continue,
+            };
+            let l = world.m.transform(inc.dir).normalize();
+            let n_dot_l = math::dot(n, l);
+            if n_dot_l <= 0. {
+                continue;
+            }
+            let h = (l + v).normalize();
+            let n_dot_h = math::dot(n, h).max(0.);
+            let h_dot_v = math::dot(h, v).max(0.);
+
+            let d = n_dot_h * n_dot_h * (a2 - 1.) + 1.;
+            let ndf = a2 / (PI * d * d);
+            let g = g_v * n_dot_l / (n_dot_l * (1. - k) + k);
+            let fresnel = f0 + (one - f0).scale((1. - h_dot_v).powi(5));
+            let specular = fresnel.scale(ndf * g / (4. * n_dot_v * n_dot_l + 1e-4));
+            // Energy not reflected is refracted and diffused, except by metals which absorb it.
+            let kd = (one - fresnel).scale(1. - metallic);
+            let diffuse = mul(kd, albedo).scale(1. / PI);
+
+            radiance = radiance + mul(diffuse + specular, inc.radiance).scale(PI * n_dot_l);
+        }
+        let radiance = radiance.scale(interpolate_f32(&self.ao, bc));
+        hdr::Rgba {
+            r: radiance.x,
+            g: radiance.y,
+            b: radiance.z,
+            a: c.a as f32 / 255.,
+        }
     }
 }
@@ -0,0 +1,118 @@
+use draw;
+use light;
+use math;
+use render;
+use shader::{self, CullMode, World};
+use wavefront;
+
+// Default depth bias, as a fraction of the depth range.
+static DEFAULT_BIAS: f32 = 0.005;
+
+// ShadowMap holds the depth of an object as seen from one of the world's lights, for shaders to
+// test fragments against.
+pub struct ShadowMap {
+    // Index of the light in the world's lights.
+    light_idx: usize,
+    // Transforms from model space to the shadow map.
+    light: World,
+    depth: draw::DepthBuffer,
+    // Depth a fragment may be behind the closest surface and still be lit, hides shadow acne from
+    // the limited resolution of the map.
+    bias: f32,
+    // Texels either side of the fragment to average over, 0 gives hard shadow edges.
+    pcf_radius: usize,
+}
+
+impl ShadowMap {
+    // new renders a size x size shadow map of obj as seen from world.lights()[light_idx].
+    // Directional lights use a parallel projection looking at center, point lights a perspective
+    // one from their position looking at center, and spot lights a perspective one along their
+    // axis wide enough for their cone.
+    pub fn new(renderer: &render::Renderer,
+               world: &World,
+               light_idx: usize,
+               obj: &wavefront::Object,
+               center: math::Vec3f,
+               size: usize)
+               -> Self {
+        let (eye, center, directional) = match world.lights()[light_idx].source {
+            light::Source::Directional { dir } => (dir.normalize() + center, center, true),
+            light::Source::Point { pos, .. } => (pos, center, false),
+            light::Source::Spot { pos, dir, outer, .. } => {
+                // The projection's field of view reaches 1 unit either side of the look at point,
+                // so put it where that's the width of the cone.  Cones can't be 180 degrees or
+                // wider, cap them short of that.
+                let outer = outer.max(0.01).min(1.5);
+                (pos, pos + dir.normalize().scale(1. / outer.tan()), false)
+            }
+        };
+        let mut up = math::Vec3f {
+            x: 0.,
+            y: 1.,
+            z: 0.,
+        };
+        if math::cross(up, (eye - center).normalize()).length() < 1e-3 {
+            // Light is straight up or down, any other up vector will do.
+            up = math::Vec3f {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            };
+        }
+        let mut light = World::new();
+        light.set_viewport(0, 0, size, size);
+        light.look_at(eye, center, up);
+        if directional {
+            light.orthographic();
+        }
+        // Back faces cast shadows too.
+        light.set_cull_mode(CullMode::None);
+
+        let mut depth = draw::DepthBuffer::new(size, size);
+        renderer.draw_depth(&light, obj, &mut depth);
+        ShadowMap {
+            light_idx: light_idx,
+            light: light,
+            depth: depth,
+            bias: DEFAULT_BIAS * shader::DEPTH_RESOLUTION,
+            pcf_radius: 1,
+        }
+    }
+
+    // Index in the world's lights of the light the map was rendered from.
+    pub fn light(&self) -> usize {
+        self.light_idx
+    }
+
+    // Sets the depth bias as a fraction of the depth range.
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias * shader::DEPTH_RESOLUTION;
+    }
+
+    // Sets the radius, in texels, of the percentage closer filter.  0 disables filtering.
+    pub fn set_pcf_radius(&mut self, pcf_radius: usize) {
+        self.pcf_radius = pcf_radius;
+    }
+
+    // visibility returns the fraction, from 0 in shadow to 1 fully lit, of the shadow map texels
+    // around model space point p that don't occlude it.  Points outside the map are lit.
+    pub fn visibility(&self, p: math::Vec3f) -> f32 {
+        let s = self.light.vp_p_mv.transform(p);
+        let (cx, cy) = (s.x.floor() as i64, s.y.floor() as i64);
+        let r = self.pcf_radius as i64;
+        let mut lit = 0;
+        for y in cy - r..cy + r + 1 {
+            for x in cx - r..cx + r + 1 {
+                if x < 0 || y < 0 || x >= self.depth.w as i64 || y >= self.depth.h as i64 {
+                    lit += 1;
+                    continue;
+                }
+                // Larger depth values are closer to the light.
+                if self.depth.get(x as usize, y as usize) <= s.z + self.bias {
+                    lit += 1;
+                }
+            }
+        }
+        lit as f32 / ((2 * r + 1) * (2 * r + 1)) as f32
+    }
+}
@@ -0,0 +1,226 @@
+use draw;
+use math;
+use stroke;
+
+// Rule deciding which regions of a self-intersecting or nested polygon are inside.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FillRule {
+    // Inside where a ray from the point crosses the outline an odd number of times, so nested
+    // outlines leave holes.
+    EvenOdd,
+    // Inside where the outline winds around the point at all, so overlaps are filled.
+    NonZero,
+}
+
+impl FillRule {
+    fn inside(&self, winding: i32) -> bool {
+        match *self {
+            FillRule::EvenOdd => winding % 2 != 0,
+            FillRule::NonZero => winding != 0,
+        }
+    }
+}
+
+// Fill is the style Image::fill_polygon fills with.  Points are in pixel coordinates as for
+// stroke::Stroke.
+#[derive(Clone, Debug)]
+pub struct Fill {
+    pub color: draw::RGB,
+    pub rule: FillRule,
+    pub antialias: bool,
+}
+
+impl Fill {
+    // Anti-aliased, non-zero fill of color.
+    pub fn new(color: draw::RGB) -> Self {
+        Fill {
+            color: color,
+            rule: FillRule::NonZero,
+            antialias: true,
+        }
+    }
+}
+
+// ellipse calls plot with the offsets from the center of the pixels on the outline of an ellipse
+// with radii rx and ry, in the quadrant where both are positive, using the midpoint algorithm.
+// Mirror them for the other quadrants.  Pixels go clockwise from 0,ry to rx,0, both included.
+pub fn ellipse<F>(rx: i64, ry: i64, mut plot: F)
+    where F: FnMut(i64, i64)
+{
+    if rx < 0 || ry < 0 {
+        return;
+    }
+    if rx == 0 || ry == 0 {
+        for y in (1..ry + 1).rev() {
+            plot(0, y);
+        }
+        for x in 0..rx + 1 {
+            plot(x, 0);
+        }
+        return;
+    }
+    let (a2, b2) = (rx * rx, ry * ry);
+    let (mut x, mut y) = (0, ry);
+    // Decision variables are 4 times the implicit function at the midpoint between the next two
+    // candidates, keeping them integers.
+    // Step along x while the outline is flatter than 45 degrees.
+    let mut d = 4 * b2 - 4 * a2 * ry + a2;
+    while b2 * x <= a2 * y {
+        plot(x, y);
+        if d < 0 {
+            d += 4 * b2 * (2 * x + 3);
+        } else {
+            d += 4 * b2 * (2 * x + 3) - 8 * a2 * (y - 1);
+            y -= 1;
+        }
+        x += 1;
+    }
+    // Then along y.
+    let mut d = b2 * (2 * x + 1) * (2 * x + 1) + 4 * a2 * (y - 1) * (y - 1) - 4 * a2 * b2;
+    while y >= 0 {
+        plot(x, y);
+        if d > 0 {
+            d -= 4 * a2 * (2 * y - 3);
+        } else {
+            d += 8 * b2 * (x + 1) - 4 * a2 * (2 * y - 3);
+            x += 1;
+        }
+        y -= 1;
+    }
+}
+
+// ellipse_spans calls span with each row of pixels, as an offset from the center and the
+// half-width either side of it, filled by the ellipse with radii rx and ry.
+pub fn ellipse_spans<F>(rx: i64, ry: i64, mut span: F)
+    where F: FnMut(i64, i64)
+{
+    if rx < 0 || ry < 0 {
+        return;
+    }
+    // The outline pixel furthest from the center on each row bounds the row.
+    let mut widths = vec![0; ry as usize + 1];
+    ellipse(rx, ry, |x, y| {
+        let w = &mut widths[y as usize];
+        *w = (*w).max(x);
+    });
+    for (y, &w) in widths.iter().enumerate() {
+        span(y as i64, w);
+        if y > 0 {
+            span(-(y as i64), w);
+        }
+    }
+}
+
+// Curves are flattened until they're at most this many pixels from their line segments.
+const FLATNESS: f32 = 0.25;
+
+// Number of segments flattening a curve with largest second difference dd, of degree n, to within
+// FLATNESS pixels (Wang's formula).
+fn segments(dd: f32, n: f32) -> usize {
+    let s = (n * (n - 1.) / 8. * dd / FLATNESS).sqrt().ceil();
+    s.max(1.).min(1000.) as usize
+}
+
+// quadratic returns points along the quadratic Bézier curve from p0 to p2 with control point p1,
+// close enough together to draw as a polyline.  Both end points are included.
+pub fn quadratic(p0: math::Vec3f, p1: math::Vec3f, p2: math::Vec3f) -> Vec<math::Vec3f> {
+    let n = segments((p0 - p1.scale(2.) + p2).length(), 2.);
+    (0..n + 1)
+        .map(|i| {
+            let t = i as f32 / n as f32;
+            let s = 1. - t;
+            p0.scale(s * s) + p1.scale(2. * s * t) + p2.scale(t * t)
+        })
+        .collect()
+}
+
+// cubic is quadratic for the cubic Bézier curve from p0 to p3 with control points p1 and p2.
+pub fn cubic(p0: math::Vec3f,
+             p1: math::Vec3f,
+             p2: math::Vec3f,
+             p3: math::Vec3f)
+             -> Vec<math::Vec3f> {
+    let dd = (p0 - p1.scale(2.) + p2).length().max((p1 - p2.scale(2.) + p3).length());
+    let n = segments(dd, 3.);
+    (0..n + 1)
+        .map(|i| {
+            let t = i as f32 / n as f32;
+            let s = 1. - t;
+            p0.scale(s * s * s) + p1.scale(3. * s * s * t) + p2.scale(3. * s * t * t) +
+            p3.scale(t * t * t)
+        })
+        .collect()
+}
+
+// Rows sampled per pixel by anti-aliased polygon fills.  Coverage along each row is exact.
+const SUB_ROWS: usize = 4;
+
+// coverage returns the pixels in clip, x_min, y_min, x_max, y_max inclusive, covered by the polygon
+// points, closed from the last point back to the first, filled with style.  Pixels are inside if
+// their center is, or for anti-aliased fills covered by the fraction of them inside.
+pub fn coverage(points: &[math::Vec3f],
+                style: &Fill,
+                clip: (i64, i64, i64, i64))
+                -> stroke::Coverage {
+    if points.len() < 3 {
+        return stroke::Coverage::new(0, 0, -1, -1);
+    }
+    let x_min = points.iter().fold(::std::f32::MAX, |m, p| m.min(p.x)).floor() as i64;
+    let y_min = points.iter().fold(::std::f32::MAX, |m, p| m.min(p.y)).floor() as i64;
+    let x_max = points.iter().fold(::std::f32::MIN, |m, p| m.max(p.x)).ceil() as i64;
+    let y_max = points.iter().fold(::std::f32::MIN, |m, p| m.max(p.y)).ceil() as i64;
+    let mut cov = stroke::Coverage::clipped((x_min, y_min, x_max, y_max), clip);
+
+    let rows = if style.antialias { SUB_ROWS } else { 1 };
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    for y in cov.y..cov.y + cov.h as i64 {
+        for r in 0..rows {
+            let sy = y as f32 + (r as f32 + 0.5) / rows as f32;
+            // Where the row crosses each edge, and which way the edge goes.  Edges include their
+            // lower end and exclude their upper one so shared vertices cross once.
+            crossings.clear();
+            for i in 0..points.len() {
+                let (a, b) = (points[i], points[(i + 1) % points.len()]);
+                let (lo, hi, dir) = if a.y < b.y { (a, b, 1) } else { (b, a, -1) };
+                if sy < lo.y || sy >= hi.y {
+                    continue;
+                }
+                crossings.push((lo.x + (sy - lo.y) * (hi.x - lo.x) / (hi.y - lo.y), dir));
+            }
+            crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+            let mut winding = 0;
+            for i in 0..crossings.len().saturating_sub(1) {
+                winding += crossings[i].1;
+                if !style.rule.inside(winding) {
+                    continue;
+                }
+                let (x0, x1) = (crossings[i].0, crossings[i + 1].0);
+                if style.antialias {
+                    span_coverage(&mut cov, y, x0, x1, 1. / rows as f32);
+                } else {
+                    // Pixels with their center in x0..x1.
+                    let start = ((x0 - 0.5).ceil() as i64).max(cov.x);
+                    let end = ((x1 - 0.5).ceil() as i64).min(cov.x + cov.w as i64);
+                    for x in start..end {
+                        cov.add(x, y, 1.);
+                    }
+                }
+            }
+        }
+    }
+    cov
+}
+
+// span_coverage adds weight times the fraction of each pixel in row y between x0 and x1.
+fn span_coverage(cov: &mut stroke::Coverage, y: i64, x0: f32, x1: f32, weight: f32) {
+    let ly = y - cov.y;
+    if ly < 0 || ly >= cov.h as i64 {
+        return;
+    }
+    for x in (x0.floor() as i64).max(cov.x)..(x1.ceil() as i64).min(cov.x + cov.w as i64) {
+        let lx = x - cov.x;
+        let overlap = (x1.min(x as f32 + 1.) - x0.max(x as f32)).max(0.);
+        let off = lx as usize + ly as usize * cov.w;
+        cov.buf[off] = (cov.buf[off] + overlap * weight).min(1.);
+    }
+}
@@ -0,0 +1,428 @@
+use draw;
+use math;
+
+// Shape drawn past the ends of an open polyline.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineCap {
+    // End exactly at the end point.
+    Butt,
+    // Extend half the width past the end point.
+    Square,
+    // Half circle around the end point.
+    Round,
+}
+
+// Shape filling the gap on the outside of a bend in a polyline.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineJoin {
+    // Extend the outer edges until they meet, beveled past Stroke::miter_limit.
+    Miter,
+    // Cut the corner straight across.
+    Bevel,
+    // Circle around the vertex.
+    Round,
+}
+
+// Stroke is the style Image::stroke draws lines with.  Points are in pixel coordinates, the center
+// of pixel x,y is at x + 0.5, y + 0.5, and the ends of lines are included.
+#[derive(Clone, Debug)]
+pub struct Stroke {
+    pub color: draw::RGB,
+    // Width in pixels.  Anti-aliased strokes up to a pixel wide use Xiaolin Wu's algorithm, faded
+    // by the width.
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    // Longest miter join, as a multiple of the width, before it's beveled instead.
+    pub miter_limit: f32,
+    // Alternating lengths in pixels of dashes and gaps, starting with a dash.  Empty draws a solid
+    // line.
+    pub dashes: Vec<f32>,
+    pub antialias: bool,
+}
+
+impl Stroke {
+    // Solid, anti-aliased, one pixel wide stroke of color.
+    pub fn new(color: draw::RGB) -> Self {
+        Stroke {
+            color: color,
+            width: 1.,
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+            miter_limit: 4.,
+            dashes: Vec::new(),
+            antialias: true,
+        }
+    }
+}
+
+// Coverage is the fraction, 0 to 1, of each pixel in a rectangle covered by a shape.
+pub struct Coverage {
+    // Pixel coordinates of the rectangle's corner, may be outside the image.
+    pub x: i64,
+    pub y: i64,
+    pub w: usize,
+    pub h: usize,
+    pub buf: Vec<f32>,
+}
+
+impl Coverage {
+    // new returns an empty coverage of the pixels x_min..x_max, y_min..y_max inclusive.
+    pub fn new(x_min: i64, y_min: i64, x_max: i64, y_max: i64) -> Self {
+        let (w, h) = ((x_max - x_min + 1).max(0) as usize, (y_max - y_min + 1).max(0) as usize);
+        Coverage {
+            x: x_min,
+            y: y_min,
+            w: w,
+            h: h,
+            buf: vec![0.; w * h],
+        }
+    }
+
+    // clipped is new for the pixels in bounds, x_min, y_min, x_max, y_max inclusive, that are also
+    // in clip, so shapes reaching far off the image don't allocate for pixels never drawn.
+    pub fn clipped(bounds: (i64, i64, i64, i64), clip: (i64, i64, i64, i64)) -> Self {
+        Coverage::new(bounds.0.max(clip.0),
+                      bounds.1.max(clip.1),
+                      bounds.2.min(clip.2),
+                      bounds.3.min(clip.3))
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> f32 {
+        let (lx, ly) = (x - self.x, y - self.y);
+        if lx < 0 || ly < 0 || lx >= self.w as i64 || ly >= self.h as i64 {
+            return 0.;
+        }
+        self.buf[lx as usize + ly as usize * self.w]
+    }
+
+    // add covers pixel x,y by c, keeping the larger where shapes overlap so they aren't drawn
+    // twice.
+    pub fn add(&mut self, x: i64, y: i64, c: f32) {
+        let (lx, ly) = (x - self.x, y - self.y);
+        if lx < 0 || ly < 0 || lx >= self.w as i64 || ly >= self.h as i64 {
+            return;
+        }
+        let off = lx as usize + ly as usize * self.w;
+        if c > self.buf[off] {
+            self.buf[off] = c.min(1.);
+        }
+    }
+
+    // fill covers the pixels in x_min..x_max, y_min..y_max inclusive by a shape whose signed
+    // distance, negative inside, at a point is sdf.  Anti-aliased coverage fades over a pixel
+    // across the edge, otherwise pixels with their center inside are fully covered.
+    pub fn fill<F>(&mut self, bounds: (i64, i64, i64, i64), antialias: bool, sdf: F)
+        where F: Fn(math::Vec3f) -> f32
+    {
+        let (x_min, y_min, x_max, y_max) = bounds;
+        for y in y_min.max(self.y)..(y_max + 1).min(self.y + self.h as i64) {
+            for x in x_min.max(self.x)..(x_max + 1).min(self.x + self.w as i64) {
+                let d = sdf(math::Vec3f {
+                    x: x as f32 + 0.5,
+                    y: y as f32 + 0.5,
+                    z: 0.,
+                });
+                let c = if antialias {
+                    (0.5 - d).max(0.).min(1.)
+                } else if d <= 0. {
+                    1.
+                } else {
+                    0.
+                };
+                if c > 0. {
+                    self.add(x, y, c);
+                }
+            }
+        }
+    }
+}
+
+fn fpart(v: f32) -> f32 {
+    v - v.floor()
+}
+
+// wu calls plot with the pixels covered by the one pixel wide line p0-p1 and their coverage, using
+// Xiaolin Wu's algorithm.  The pixels the end points are in are included.
+pub fn wu<F>(p0: math::Vec3f, p1: math::Vec3f, mut plot: F)
+    where F: FnMut(i64, i64, f32)
+{
+    // Work with pixel centers on integers.
+    let (mut x0, mut y0, mut x1, mut y1) = (p0.x - 0.5, p0.y - 0.5, p1.x - 0.5, p1.y - 0.5);
+    let steep = (y1 - y0).abs() > (x1 - x0).abs();
+    if steep {
+        ::std::mem::swap(&mut x0, &mut y0);
+        ::std::mem::swap(&mut x1, &mut y1);
+    }
+    if x0 > x1 {
+        ::std::mem::swap(&mut x0, &mut x1);
+        ::std::mem::swap(&mut y0, &mut y1);
+    }
+    let mut plot = |x: i64, y: i64, c: f32| if steep { plot(y, x, c) } else { plot(x, y, c) };
+    let dx = x1 - x0;
+    let gradient = if dx == 0. { 1. } else { (y1 - y0) / dx };
+
+    // The line is extended half a pixel past each end point, so an end point on a pixel center
+    // covers its whole pixel.
+    let x_start = x0.round();
+    let y_start = y0 + gradient * (x_start - x0);
+    let gap = (x_start - x0 + 1.).min(1.);
+    plot(x_start as i64, y_start.floor() as i64, (1. - fpart(y_start)) * gap);
+    plot(x_start as i64, y_start.floor() as i64 + 1, fpart(y_start) * gap);
+
+    let x_end = x1.round();
+    if x_end > x_start {
+        let y_end = y1 + gradient * (x_end - x1);
+        let gap = (x1 - x_end + 1.).min(1.);
+        plot(x_end as i64, y_end.floor() as i64, (1. - fpart(y_end)) * gap);
+        plot(x_end as i64, y_end.floor() as i64 + 1, fpart(y_end) * gap);
+    }
+
+    let mut y = y_start + gradient;
+    for x in x_start as i64 + 1..x_end as i64 {
+        plot(x, y.floor() as i64, 1. - fpart(y));
+        plot(x, y.floor() as i64 + 1, fpart(y));
+        y += gradient;
+    }
+}
+
+// solid returns true if dashes draws a solid line, as does any invalid pattern.
+fn solid(dashes: &[f32]) -> bool {
+    dashes.is_empty() || dashes.iter().any(|&d| d < 0.) || dashes.iter().all(|&d| d == 0.)
+}
+
+// dash splits the polyline points into the polylines of its dashes.
+fn dash(points: &[math::Vec3f], dashes: &[f32]) -> Vec<Vec<math::Vec3f>> {
+    if solid(dashes) {
+        return vec![points.to_vec()];
+    }
+    // An odd number of lengths is repeated so dashes and gaps alternate.
+    let dashes: Vec<f32> = if dashes.len() % 2 == 1 {
+        dashes.iter().chain(dashes.iter()).cloned().collect()
+    } else {
+        dashes.to_vec()
+    };
+    let mut out = Vec::new();
+    let mut current = vec![points[0]];
+    // Index into dashes and how much of it is left, even indices are dashes.
+    let (mut i, mut left) = (0, dashes[0]);
+    for seg in points.windows(2) {
+        let (a, b) = (seg[0], seg[1]);
+        let len = (b - a).length();
+        let mut t = 0.;
+        while len - t > left {
+            t += left;
+            let p = a + (b - a).scale(t / len);
+            if i % 2 == 0 {
+                current.push(p);
+                out.push(current);
+                current = Vec::new();
+            } else {
+                current = vec![p];
+            }
+            i = (i + 1) % dashes.len();
+            left = dashes[i];
+        }
+        left -= len - t;
+        if i % 2 == 0 {
+            current.push(b);
+        }
+    }
+    if i % 2 == 0 && current.len() > 1 {
+        out.push(current);
+    }
+    out
+}
+
+// Signed distance from p to the convex polygon poly, negative inside.  Outside it's the distance to
+// the furthest edge line, exact along edges and slightly short at corners, enough for
+// anti-aliasing.
+fn convex_sdf(poly: &[math::Vec3f], p: math::Vec3f) -> f32 {
+    let mut area = 0.;
+    for i in 0..poly.len() {
+        let (a, b) = (poly[i], poly[(i + 1) % poly.len()]);
+        area += a.x * b.y - b.x * a.y;
+    }
+    let s = if area < 0. { -1. } else { 1. };
+    let mut d = ::std::f32::MIN;
+    for i in 0..poly.len() {
+        let (a, b) = (poly[i], poly[(i + 1) % poly.len()]);
+        let e = b - a;
+        let len = e.length();
+        if len == 0. {
+            continue;
+        }
+        d = d.max(-s * (e.x * (p.y - a.y) - e.y * (p.x - a.x)) / len);
+    }
+    d
+}
+
+fn poly_bounds(poly: &[math::Vec3f]) -> (i64, i64, i64, i64) {
+    let x_min = poly.iter().fold(::std::f32::MAX, |m, p| m.min(p.x));
+    let y_min = poly.iter().fold(::std::f32::MAX, |m, p| m.min(p.y));
+    let x_max = poly.iter().fold(::std::f32::MIN, |m, p| m.max(p.x));
+    let y_max = poly.iter().fold(::std::f32::MIN, |m, p| m.max(p.y));
+    ((x_min.floor() as i64).saturating_sub(1),
+     (y_min.floor() as i64).saturating_sub(1),
+     x_max.ceil() as i64,
+     y_max.ceil() as i64)
+}
+
+fn fill_polygon(cov: &mut Coverage, poly: &[math::Vec3f], antialias: bool) {
+    cov.fill(poly_bounds(poly), antialias, |p| convex_sdf(poly, p));
+}
+
+fn fill_circle(cov: &mut Coverage, c: math::Vec3f, r: f32, antialias: bool) {
+    let bounds = ((c.x - r).floor() as i64 - 1,
+                  (c.y - r).floor() as i64 - 1,
+                  (c.x + r).ceil() as i64,
+                  (c.y + r).ceil() as i64);
+    cov.fill(bounds, antialias, |p| (p - c).length() - r);
+}
+
+// Unit direction of a-b and its left normal.
+fn frame(a: math::Vec3f, b: math::Vec3f) -> (math::Vec3f, math::Vec3f) {
+    let u = (b - a).normalize();
+    (u,
+     math::Vec3f {
+         x: -u.y,
+         y: u.x,
+         z: 0.,
+     })
+}
+
+// thick adds the coverage of the polyline points drawn wider than a pixel.
+fn thick(cov: &mut Coverage, points: &[math::Vec3f], style: &Stroke) {
+    let hw = style.width / 2.;
+    let aa = style.antialias;
+    // Drop repeated points, they have no direction.
+    let mut pts: Vec<math::Vec3f> = Vec::with_capacity(points.len());
+    for &p in points {
+        if pts.last().map_or(true, |&q| (p - q).length() > 1e-6) {
+            pts.push(p);
+        }
+    }
+    if pts.len() < 2 {
+        if style.cap == LineCap::Round && !pts.is_empty() {
+            fill_circle(cov, pts[0], hw, aa);
+        }
+        return;
+    }
+    let last = pts.len() - 2;
+    for (i, seg) in pts.windows(2).enumerate() {
+        let (a, b) = (seg[0], seg[1]);
+        let (u, n) = frame(a, b);
+        let ext = if style.cap == LineCap::Square { hw } else { 0. };
+        let a = if i == 0 { a - u.scale(ext) } else { a };
+        let b = if i == last { b + u.scale(ext) } else { b };
+        fill_polygon(cov,
+                     &[a + n.scale(hw), a - n.scale(hw), b - n.scale(hw), b + n.scale(hw)],
+                     aa);
+    }
+    if style.cap == LineCap::Round {
+        fill_circle(cov, pts[0], hw, aa);
+        fill_circle(cov, pts[pts.len() - 1], hw, aa);
+    }
+    for w in pts.windows(3) {
+        let (a, v, b) = (w[0], w[1], w[2]);
+        let (ua, na) = frame(a, v);
+        let (ub, nb) = frame(v, b);
+        // The outside of the bend is to the right of a left turn.
+        let turn = ua.x * ub.y - ua.y * ub.x;
+        if turn.abs() < 1e-6 && math::dot(ua, ub) > 0. {
+            continue;
+        }
+        let side = if turn > 0. { -1. } else { 1. };
+        let (oa, ob) = (v + na.scale(hw * side), v + nb.scale(hw * side));
+        match style.join {
+            LineJoin::Round => fill_circle(cov, v, hw, aa),
+            LineJoin::Bevel => fill_polygon(cov, &[v, oa, ob], aa),
+            LineJoin::Miter => {
+                // The tip is along the bisector of the two normals, 1 / cos(half the angle
+                // between them) half widths out.
+                let bisector = na.scale(side) + nb.scale(side);
+                let cos_half = bisector.length() / 2.;
+                if cos_half > 1e-6 && 1. / cos_half <= style.miter_limit {
+                    let tip = v + bisector.normalize().scale(hw / cos_half);
+                    fill_polygon(cov, &[v, oa, tip, ob], aa);
+                } else {
+                    fill_polygon(cov, &[v, oa, ob], aa);
+                }
+            }
+        }
+    }
+}
+
+// coverage returns the pixels in clip, x_min, y_min, x_max, y_max inclusive, covered by the
+// polyline points drawn with style.
+pub fn coverage(points: &[math::Vec3f], style: &Stroke, clip: (i64, i64, i64, i64)) -> Coverage {
+    polyline(points, false, style, clip)
+}
+
+// coverage_closed is coverage for the outline of the polygon points, joining the last point back
+// to the first.
+pub fn coverage_closed(points: &[math::Vec3f],
+                       style: &Stroke,
+                       clip: (i64, i64, i64, i64))
+                       -> Coverage {
+    polyline(points, true, style, clip)
+}
+
+fn polyline(points: &[math::Vec3f],
+            closed: bool,
+            style: &Stroke,
+            clip: (i64, i64, i64, i64))
+            -> Coverage {
+    if points.is_empty() {
+        return Coverage::new(0, 0, -1, -1);
+    }
+    let reach = if style.join == LineJoin::Miter {
+        style.width * style.miter_limit
+    } else {
+        style.width
+    };
+    let (x_min, y_min, x_max, y_max) = poly_bounds(points);
+    let r = reach.ceil() as i64 + 1;
+    let mut cov = Coverage::clipped((x_min.saturating_sub(r),
+                                     y_min.saturating_sub(r),
+                                     x_max.saturating_add(r),
+                                     y_max.saturating_add(r)),
+                                    clip);
+
+    let mut points = points.to_vec();
+    if closed {
+        points.push(points[0]);
+    }
+    let is_solid = solid(&style.dashes);
+    for mut line in dash(&points, &style.dashes) {
+        if style.width > 1. {
+            if closed && is_solid && line.len() > 2 {
+                // Going around to the second point again joins the ends instead of capping them.
+                let second = line[1];
+                line.push(second);
+                thick(&mut cov,
+                      &line,
+                      &Stroke { cap: LineCap::Butt, ..style.clone() });
+            } else {
+                thick(&mut cov, &line, style);
+            }
+            continue;
+        }
+        for seg in line.windows(2) {
+            if style.antialias {
+                let fade = style.width.max(0.);
+                wu(seg[0], seg[1], |x, y, c| cov.add(x, y, c * fade));
+            } else {
+                // Step along the major axis, one pixel per step, ends included.
+                let (a, b) = (seg[0], seg[1]);
+                let d = b - a;
+                let steps = d.x.abs().max(d.y.abs()).round().max(1.) as usize;
+                for i in 0..steps + 1 {
+                    let p = a + d.scale(i as f32 / steps as f32);
+                    cov.add(p.x.floor() as i64, p.y.floor() as i64, 1.);
+                }
+            }
+        }
+    }
+    cov
+}
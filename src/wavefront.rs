@@ -19,6 +19,8 @@ struct FaceIndex {
     v_idxs: Vec<usize>,
     t_idxs: Vec<usize>,
     n_idxs: Vec<usize>,
+    // See Face::edges.
+    edges: [bool; 3],
 }
 
 impl FaceIndex {
@@ -27,6 +29,22 @@ impl FaceIndex {
             v_idxs: Vec::new(),
             t_idxs: Vec::new(),
             n_idxs: Vec::new(),
+            edges: [true, true, true],
+        }
+    }
+
+    // triangle returns the triangle made of the polygon's corners a, b and c.
+    fn triangle(&self, a: usize, b: usize, c: usize) -> FaceIndex {
+        let pick = |idxs: &Vec<usize>| if idxs.is_empty() {
+            Vec::new()
+        } else {
+            vec![idxs[a], idxs[b], idxs[c]]
+        };
+        FaceIndex {
+            v_idxs: pick(&self.v_idxs),
+            t_idxs: pick(&self.t_idxs),
+            n_idxs: pick(&self.n_idxs),
+            edges: [true, true, true],
         }
     }
 }
@@ -43,9 +61,16 @@ impl fmt::Display for FaceIndex {
 
 // TODO(wathiede): rename 'Triangle'?
 pub struct Face {
+    // Index of the face in its Object, in iteration order.
+    pub idx: usize,
     pub vertices: [Vec3f; 3],
     pub texcoords: [Vec3f; 3],
     pub normals: [Vec3f; 3],
+    // Baked ambient occlusion at each vertex, 1 when unoccluded or not baked.
+    pub ao: [f32; 3],
+    // Whether edge i, from vertex i to vertex (i + 1) % 3, is an edge of the polygon in the file
+    // rather than a diagonal added splitting it into triangles.
+    pub edges: [bool; 3],
 }
 
 impl fmt::Display for Face {
@@ -137,6 +162,15 @@ pub struct Object {
     // and speculator maps.
     tex: draw::Texture2D,
     normal_map: draw::Texture2D,
+    // Optional per-texel specular exponent.
+    spec_map: Option<draw::Texture2D>,
+    // Optional per-texel metalness and roughness for physically based shading.
+    metallic_map: Option<draw::Texture2D>,
+    roughness_map: Option<draw::Texture2D>,
+    // How the diffuse and normal maps are filtered by the *_sample_grad methods.
+    sampler: draw::Sampler,
+    // Per-vertex ambient occlusion, empty until baked.
+    ao: Vec<f32>,
 }
 
 impl Object {
@@ -151,21 +185,42 @@ impl Object {
         pb.set_file_name(p.file_stem().unwrap().to_string_lossy().to_string() + "_nm_tangent");
         pb.set_extension("tga");
         let normal_map = try!(draw::Texture2D::read(pb.as_path()));
-        let mut obj = Object {
+
+        let mut obj = Object::new(tex, normal_map);
+        obj.spec_map = try!(Object::read_optional_map(p, "_spec"));
+        obj.metallic_map = try!(Object::read_optional_map(p, "_metallic"));
+        obj.roughness_map = try!(Object::read_optional_map(p, "_roughness"));
+
+        let f = try!(File::open(p));
+        try!(obj.parse(BufReader::new(f)));
+        Ok(obj)
+    }
+
+    // new returns an object without any faces, textured with tex and normal_map, for meshes built
+    // in memory with parse.
+    pub fn new(tex: draw::Texture2D, normal_map: draw::Texture2D) -> Self {
+        Object {
             vertices: Vec::new(),
             texcoords: Vec::new(),
             normals: Vec::new(),
             faces: Vec::new(),
             tex: tex,
             normal_map: normal_map,
-        };
+            spec_map: None,
+            metallic_map: None,
+            roughness_map: None,
+            sampler: draw::Sampler::new(draw::Filter::Nearest),
+            ao: Vec::new(),
+        }
+    }
 
-        let f = try!(File::open(p));
-        let f = BufReader::new(f);
-        for line in f.lines() {
+    // parse adds the vertices, texture coordinates, normals and faces in the Wavefront OBJ data
+    // read from r.
+    pub fn parse<R: BufRead>(&mut self, r: R) -> Result<(), ObjectError> {
+        for line in r.lines() {
             match line {
                 Ok(l) => {
-                    try!(obj.parse_line(l));
+                    try!(self.parse_line(l));
                 }
                 Err(e) => {
                     return Err(ObjectError {
@@ -175,14 +230,58 @@ impl Object {
                 }
             }
         }
+        Ok(())
+    }
 
-        Ok(obj)
+    // read_optional_map reads the texture next to the object file p with suffix appended to its
+    // stem, or returns None if there is no such file.
+    fn read_optional_map(p: &Path, suffix: &str) -> Result<Option<draw::Texture2D>, ObjectError> {
+        let mut pb = p.to_path_buf();
+        pb.set_file_name(p.file_stem().unwrap().to_string_lossy().to_string() + suffix);
+        pb.set_extension("tga");
+        if !pb.exists() {
+            info!("No {} map {:?}", suffix, pb);
+            return Ok(None);
+        }
+        Ok(Some(try!(draw::Texture2D::read(pb.as_path()))))
     }
 
     pub fn vertex(&self, idx: usize) -> Vec3f {
         self.vertices[idx].clone()
     }
 
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    // vertex_normals returns a normal per vertex, the average of the normals given for the vertex by
+    // every face using it.
+    pub fn vertex_normals(&self) -> Vec<Vec3f> {
+        let mut ns = vec![Vec3f::zero(); self.vertices.len()];
+        for f in &self.faces {
+            for (&v, &n) in f.v_idxs.iter().zip(f.n_idxs.iter()) {
+                ns[v] = ns[v] + self.normals[n];
+            }
+        }
+        ns.iter()
+            .map(|n| if n.length() > 0. { n.normalize() } else { *n })
+            .collect()
+    }
+
+    // Sets the ambient occlusion of each vertex, as produced by bake::ambient_occlusion.
+    pub fn set_ambient_occlusion(&mut self, ao: Vec<f32>) {
+        assert_eq!(ao.len(), self.vertices.len());
+        self.ao = ao;
+    }
+
+    fn vertex_ao(&self, idx: usize) -> f32 {
+        if self.ao.is_empty() { 1. } else { self.ao[idx] }
+    }
+
     pub fn texcoord(&self, idx: usize) -> Vec3f {
         self.texcoords[idx].clone()
     }
@@ -196,13 +295,58 @@ impl Object {
         self.tex.sample(uv)
     }
     pub fn normal_sample(&self, uv: Vec3f) -> Vec3f {
-        let rgb = self.normal_map.sample(uv);
+        Object::decode_normal(self.normal_map.sample(uv))
+    }
+
+    // Sets the sampler used by diffuse_sample_grad and normal_sample_grad.
+    pub fn set_sampler(&mut self, sampler: draw::Sampler) {
+        self.sampler = sampler;
+    }
+
+    // Samples the diffuse map at uv, filtered by the object's sampler.  duv_dx and duv_dy are the
+    // change in uv one pixel right and down on screen.
+    pub fn diffuse_sample_grad(&self, uv: Vec3f, duv_dx: Vec3f, duv_dy: Vec3f) -> draw::RGB {
+        self.tex.sample_with(&self.sampler, uv, duv_dx, duv_dy)
+    }
+
+    // Samples the diffuse map like diffuse_sample_grad, including its alpha.
+    pub fn diffuse_rgba_grad(&self, uv: Vec3f, duv_dx: Vec3f, duv_dy: Vec3f) -> draw::RGBA {
+        self.tex.sample_rgba_with(&self.sampler, uv, duv_dx, duv_dy)
+    }
+
+    // Samples the normal map like diffuse_sample_grad.
+    pub fn normal_sample_grad(&self, uv: Vec3f, duv_dx: Vec3f, duv_dy: Vec3f) -> Vec3f {
+        Object::decode_normal(self.normal_map.sample_with(&self.sampler, uv, duv_dx, duv_dy))
+    }
+
+    fn decode_normal(rgb: draw::RGB) -> Vec3f {
         Vec3f {
             x: rgb.b as f32 / 255. * 2. - 1.,
             y: rgb.g as f32 / 255. * 2. - 1.,
             z: rgb.r as f32 / 255. * 2. - 1.,
         }
     }
+    // Samples the specular map at uv, returning the specular exponent, 5 for black texels up to 260
+    // for white ones as tinyrenderer's maps expect, or None if the object has no specular map.
+    pub fn specular_sample(&self, uv: Vec3f) -> Option<f32> {
+        self.spec_map.as_ref().map(|t| 5. + t.sample(uv).r as f32)
+    }
+
+    // Sets the map of specular exponents sampled by specular_sample.
+    pub fn set_specular_map(&mut self, spec_map: draw::Texture2D) {
+        self.spec_map = Some(spec_map);
+    }
+    // Samples the metallic map at uv, 0 for dielectrics and 1 for metals, or None if the object has
+    // no metallic map.
+    pub fn metallic_sample(&self, uv: Vec3f) -> Option<f32> {
+        self.metallic_map.as_ref().map(|t| t.sample(uv).r as f32 / 255.)
+    }
+
+    // Samples the roughness map at uv, 0 for smooth and 1 for rough, or None if the object has no
+    // roughness map.
+    pub fn roughness_sample(&self, uv: Vec3f) -> Option<f32> {
+        self.roughness_map.as_ref().map(|t| t.sample(uv).r as f32 / 255.)
+    }
     fn parse_line(&mut self, l: String) -> Result<(), ObjectError> {
         let p: Vec<_> = l.split_whitespace().collect();
         if p.is_empty() {
@@ -221,8 +365,7 @@ impl Object {
 
     fn add_face(&mut self, p: Vec<&str>) -> Result<(), ObjectError> {
         debug!("Face {:?}", p);
-        // TODO(wathiede): add support for quad faces, triangles only for now.
-        if p.len() != 4 {
+        if p.len() < 4 {
             return Err(ObjectError {
                 desc: "Bad vertex line",
                 cause: ErrorRepr::ParseError(p.join(" ")),
@@ -241,7 +384,13 @@ impl Object {
                 }
             }
         }
-        self.faces.push(f);
+        // Split polygons into a fan of triangles around the first corner.
+        let n = f.v_idxs.len();
+        for i in 1..n - 1 {
+            let mut tri = f.triangle(0, i, i + 1);
+            tri.edges = [i == 1, true, i + 1 == n - 1];
+            self.faces.push(tri);
+        }
         Ok(())
     }
 
@@ -304,6 +453,7 @@ impl<'a> iter::Iterator for ObjectIter<'a> {
         let ref f_idx = self.obj.faces[self.idx];
         // TODO(wathiede): add texcoord/normal values.
         let face = Face {
+            idx: self.idx,
             vertices: [self.obj.vertex(f_idx.v_idxs[0]),
                        self.obj.vertex(f_idx.v_idxs[1]),
                        self.obj.vertex(f_idx.v_idxs[2])],
@@ -313,6 +463,10 @@ impl<'a> iter::Iterator for ObjectIter<'a> {
             normals: [self.obj.normal(f_idx.n_idxs[0]),
                       self.obj.normal(f_idx.n_idxs[1]),
                       self.obj.normal(f_idx.n_idxs[2])],
+            ao: [self.obj.vertex_ao(f_idx.v_idxs[0]),
+                 self.obj.vertex_ao(f_idx.v_idxs[1]),
+                 self.obj.vertex_ao(f_idx.v_idxs[2])],
+            edges: f_idx.edges,
         };
         self.idx += 1;
         Some(face)
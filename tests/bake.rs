@@ -0,0 +1,59 @@
+extern crate math;
+extern crate rustyrenderer;
+
+mod common;
+
+use rustyrenderer::{bake, render};
+
+// Checks a vertex in the inside corner where a floor meets a wall sees less of the sky than the
+// floor's far, exposed corner.
+#[test]
+fn crease_darker_than_corner() {
+    // The floor, y = -0.5, has columns of vertices at the wall, in the crease just beside it and at
+    // its far edge, in three rows along z.  The wall, x = -0.5, shares the floor's first column and
+    // rises to y = 0.5.
+    let (xs, zs) = ([-0.5, -0.4, 0.5], [-0.9, 0., 0.9]);
+    let mut src = String::new();
+    for z in &zs {
+        for x in &xs {
+            src += &format!("v {} -0.5 {}\n", x, z);
+        }
+    }
+    for z in &zs {
+        src += &format!("v -0.5 0.5 {}\n", z);
+    }
+    src += "vt 0 0 0\nvn 0 1 0\nvn 1 0 0\n";
+    // Indices are 1-based.
+    let floor = |i: usize, j: usize| i + j * 3 + 1;
+    let wall = |j: usize| 10 + j;
+    for j in 0..2 {
+        for i in 0..2 {
+            src += &format!("f {}/1/1 {}/1/1 {}/1/1 {}/1/1\n",
+                            floor(i, j),
+                            floor(i, j + 1),
+                            floor(i + 1, j + 1),
+                            floor(i + 1, j));
+        }
+        src += &format!("f {}/1/2 {}/1/2 {}/1/2 {}/1/2\n",
+                        floor(0, j),
+                        wall(j),
+                        wall(j + 1),
+                        floor(0, j + 1));
+    }
+    let obj = common::object(&src);
+    let ao = bake::ambient_occlusion(&render::Renderer::new(), &obj, 128, 128);
+    let (crease, corner) = (ao[floor(1, 1) - 1], ao[floor(2, 2) - 1]);
+    assert!(crease < corner - 0.2, "crease {} corner {}", crease, corner);
+    assert!(corner > 0.8, "corner {}", corner);
+}
+
+// Checks a triangle on its own isn't occluded from the side it faces.
+#[test]
+fn lone_triangle_unoccluded() {
+    let obj = common::object("v -0.5 -0.5 0\nv 0.5 -0.5 0\nv 0 0.5 0\nvt 0 0 0\nvn 0 0 1
+f 1/1/1 2/1/1 3/1/1\n");
+    let ao = bake::ambient_occlusion(&render::Renderer::new(), &obj, 64, 128);
+    for (i, &a) in ao.iter().enumerate() {
+        assert!(a > 0.95, "vertex {}: {:?}", i, ao);
+    }
+}
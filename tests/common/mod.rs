@@ -0,0 +1,17 @@
+use std::io::Cursor;
+
+use rustyrenderer::draw;
+use rustyrenderer::wavefront::Object;
+
+// texture returns a 2x2 texture of the single RGBA color c.
+pub fn texture(c: [u8; 4]) -> draw::Texture2D {
+    draw::Texture2D::new(2, 2, c.iter().cycle().take(16).cloned().collect())
+}
+
+// object parses the Wavefront OBJ data src into an object with a flat mid grey diffuse map and a
+// normal map pointing straight out of the surface.
+pub fn object(src: &str) -> Object {
+    let mut obj = Object::new(texture([128, 128, 128, 255]), texture([255, 128, 128, 255]));
+    obj.parse(Cursor::new(src)).unwrap();
+    obj
+}
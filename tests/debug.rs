@@ -0,0 +1,81 @@
+extern crate math;
+extern crate rustyrenderer;
+
+mod common;
+
+use math::Vec3f;
+use rustyrenderer::{debug, draw, render, shader};
+
+const SIZE: usize = 64;
+
+// Checks overdraw counts two where triangles overlap, one where only one covers and none outside,
+// and the heatmap is hottest where they overlap.
+#[test]
+fn overdraw_overlap() {
+    // Two triangles sharing the left half of the viewport, projection left as the identity.
+    let obj = common::object("v -1 -1 0\nv 0.5 -1 0\nv -1 1 0\nv -0.5 -1 0\nv 0 -1 0\nv -0.5 1 0
+vt 0 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\nf 4/1/1 5/1/1 6/1/1\n");
+    let mut world = shader::World::new();
+    world.set_viewport(0, 0, SIZE, SIZE);
+    let counts = debug::overdraw_counts(&world, &obj, SIZE, SIZE);
+    let count = |x: usize, y: usize| counts[x + y * SIZE];
+    // Inside both, inside only the first, and outside both.
+    let (both, one, none) = ((17, 8), (4, 8), (60, 60));
+    assert_eq!(count(both.0, both.1), 2);
+    assert_eq!(count(one.0, one.1), 1);
+    assert_eq!(count(none.0, none.1), 0);
+    assert_eq!(counts.iter().cloned().max(), Some(2));
+
+    let im = debug::overdraw(&world, &obj, SIZE, SIZE);
+    let rgb = |x: usize, y: usize| {
+        let c = im.get(x, y);
+        (c.r, c.g, c.b)
+    };
+    assert_eq!(rgb(both.0, both.1), (255, 0, 0));
+    assert_eq!(rgb(none.0, none.1), (0, 0, 0));
+    assert!(rgb(one.0, one.1) != (255, 0, 0) && rgb(one.0, one.1) != (0, 0, 0));
+}
+
+// Checks DepthShader's gray falls linearly with distance from the camera, for squares facing the
+// camera at three distances.
+#[test]
+fn depth_linear_in_distance() {
+    // Squares 2, 3 and 4 from the camera, spread out so they don't hide each other.
+    let mut src = String::new();
+    for &(x, z) in &[(-0.6, 1.), (0., 0.), (0.6, -1.)] {
+        for &(dx, dy) in &[(-0.15, -0.15), (0.15, -0.15), (0.15, 0.15), (-0.15, 0.15)] {
+            src += &format!("v {} {} {}\n", x + dx, dy, z);
+        }
+    }
+    src += "vt 0 0 0\nvn 0 0 1\n";
+    for i in 0..3 {
+        let v = i * 4 + 1;
+        src += &format!("f {}/1/1 {}/1/1 {}/1/1 {}/1/1\n", v, v + 1, v + 2, v + 3);
+    }
+    let obj = common::object(&src);
+
+    let mut world = shader::World::new();
+    world.set_viewport(0, 0, SIZE, SIZE);
+    let eye = Vec3f { x: 0., y: 0., z: 3. };
+    world.look_at(eye, Vec3f::zero(), Vec3f { x: 0., y: 1., z: 0. });
+    let mut im = draw::Image::new(SIZE, SIZE);
+    let mut z_buffer = draw::DepthBuffer::new(SIZE, SIZE);
+    let shdr = debug::DepthShader::new(1., 5.);
+    render::Renderer::new().draw(&world, &obj, &shdr, &mut im, &mut z_buffer);
+
+    // Where each square's center lands on screen, then the gray there.
+    let gray: Vec<i32> = [(-0.6, 1.), (0., 0.), (0.6, -1.)]
+        .iter()
+        .map(|&(x, z)| {
+            let p = world.vp_p_mv.transform(Vec3f { x: x, y: 0., z: z });
+            im.get(p.x as usize, p.y as usize).r as i32
+        })
+        .collect();
+    // White at 1, black at 5.
+    for (i, &g) in gray.iter().enumerate() {
+        let d = 2. + i as f32;
+        let want = ((1. - (d - 1.) / 4.) * 255.) as i32;
+        assert!((g - want).abs() <= 1, "distance {}: {} want {}", d, g, want);
+    }
+    assert!(gray[0] > gray[1] && gray[1] > gray[2], "{:?}", gray);
+}
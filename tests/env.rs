@@ -0,0 +1,70 @@
+extern crate math;
+extern crate rustyrenderer;
+
+use std::fs;
+
+use math::Vec3f;
+use rustyrenderer::{color, draw, env, hdr};
+
+// Normals, not all unit length, to check irradiance in.
+const NORMALS: [(f32, f32, f32); 6] = [(0., 1., 0.),
+                                       (0., -1., 0.),
+                                       (1., 0., 0.),
+                                       (0., 0., -2.),
+                                       (0.3, 0.5, -0.8),
+                                       (-1., 1., 1.)];
+
+// near checks every channel of c is within 2% of want.
+fn near(c: Vec3f, want: f32) -> bool {
+    [c.x, c.y, c.z].iter().all(|&v| (v - want).abs() <= want * 0.02)
+}
+
+// Checks a constant environment gives that constant as irradiance in every direction, brighter than
+// white from float data.
+#[test]
+fn constant_irradiance() {
+    let grey = env::EnvironmentMap::from_texture(draw::Texture2D::new(64,
+                                                                      32,
+                                                                      vec![200; 64 * 32 * 4]));
+    let mut im = hdr::HdrImage::new(64, 32);
+    for v in im.buf.iter_mut() {
+        *v = 3.;
+    }
+    let bright = env::EnvironmentMap::from_hdr(&im);
+    for &(x, y, z) in &NORMALS {
+        let n = Vec3f { x: x, y: y, z: z };
+        let want = color::srgb_to_linear(200);
+        assert!(near(grey.irradiance(n), want), "{:?}: {:?}", n, grey.irradiance(n));
+        assert!(near(bright.irradiance(n), 3.), "{:?}: {:?}", n, bright.irradiance(n));
+        assert!(near(bright.sample_glossy(n, 1.), 3.));
+    }
+}
+
+// Checks light above 1 survives loading an environment map from a float image file.
+#[test]
+fn read_hdr_keeps_light_above_one() {
+    let mut im = hdr::HdrImage::new(32, 16);
+    for y in 0..16 {
+        for x in 0..32 {
+            // The sky, the upper half, is much brighter than white.
+            let v = if y >= 8 { 50. } else { 0.5 };
+            im.set(x,
+                   y,
+                   hdr::Rgba {
+                       r: v,
+                       g: v,
+                       b: v,
+                       a: 1.,
+                   });
+        }
+    }
+    let path = std::env::temp_dir().join(format!("rustyrenderer-{}-sky.pfm", std::process::id()));
+    im.save(&path).unwrap();
+    let map = env::EnvironmentMap::read_hdr(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let up = Vec3f { x: 0., y: 1., z: 0. };
+    assert_eq!(map.sample(up).x, 50.);
+    assert_eq!(map.sample(up.scale(-1.)).x, 0.5);
+    assert!(map.irradiance(up).x > 20.);
+}
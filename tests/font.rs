@@ -0,0 +1,55 @@
+extern crate rustyrenderer;
+
+use rustyrenderer::draw;
+use rustyrenderer::font::{self, Align, Text};
+
+// Checks every printable character has its own glyph, and only space is blank.
+#[test]
+fn glyphs_distinct() {
+    let chars: Vec<char> = (32u8..127).map(|c| c as char).collect();
+    for (i, &a) in chars.iter().enumerate() {
+        assert_eq!(font::glyph(a).iter().all(|&r| r == 0), a == ' ', "{:?}", a);
+        for &b in &chars[i + 1..] {
+            assert!(font::glyph(a) != font::glyph(b), "{:?} and {:?} match", a, b);
+        }
+    }
+    assert_eq!(font::glyph('é'), font::glyph('?'));
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> draw::RGB {
+    draw::RGB { r: r, g: g, b: b }
+}
+
+// Checks scaled, right aligned text and its background land where measured, y up.
+#[test]
+fn draw_text() {
+    let mut style = Text::new(rgb(255, 255, 255));
+    style.scale = 2;
+    style.align = Align::Right;
+    style.background = Some(rgb(0, 0, 255));
+    assert_eq!(font::measure("HI", 2), (22, 14));
+    assert_eq!(font::measure("HI\nthere", 1), (29, 16));
+
+    let mut im = draw::Image::new(40, 24);
+    im.draw_text(30, 4, "HI", &style);
+    let at = |x, y| {
+        let c = im.get(x, y);
+        (c.r, c.g, c.b)
+    };
+    let (fg, bg, black) = ((255, 255, 255), (0, 0, 255), (0, 0, 0));
+    // Bottom and top left corners of the H.
+    assert_eq!(at(8, 4), fg);
+    assert_eq!(at(9, 5), fg);
+    assert_eq!(at(8, 17), fg);
+    assert_eq!(at(10, 4), bg);
+    // Second row of the I, only its middle column.
+    assert_eq!(at(24, 14), fg);
+    assert_eq!(at(22, 14), bg);
+    // Padding and beyond.
+    assert_eq!(at(6, 2), bg);
+    assert_eq!(at(31, 19), bg);
+    assert_eq!(at(5, 4), black);
+    assert_eq!(at(32, 4), black);
+    assert_eq!(at(8, 1), black);
+    assert_eq!(at(8, 20), black);
+}
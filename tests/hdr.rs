@@ -0,0 +1,75 @@
+extern crate rustyrenderer;
+
+use rustyrenderer::color;
+use rustyrenderer::hdr::{HdrImage, Rgba, ToneMap};
+
+// resolved returns the red channel of a single pixel of linear value v resolved with tone_map and
+// exposure.
+fn resolved(v: f32, tone_map: ToneMap, exposure: f32) -> u8 {
+    let mut im = HdrImage::new(1, 1);
+    im.set(0,
+           0,
+           Rgba {
+               r: v,
+               g: v,
+               b: v,
+               a: 1.,
+           });
+    im.resolve(tone_map, exposure).get(0, 0).r
+}
+
+#[test]
+fn clamp() {
+    assert_eq!(resolved(0., ToneMap::Clamp, 1.), 0);
+    assert_eq!(resolved(0.5, ToneMap::Clamp, 1.), color::linear_to_srgb(0.5));
+    assert_eq!(resolved(1., ToneMap::Clamp, 1.), 255);
+    assert_eq!(resolved(4., ToneMap::Clamp, 1.), 255);
+    assert_eq!(resolved(-1., ToneMap::Clamp, 1.), 0);
+}
+
+#[test]
+fn reinhard() {
+    assert_eq!(resolved(1., ToneMap::Reinhard, 1.), color::linear_to_srgb(0.5));
+    assert_eq!(resolved(3., ToneMap::Reinhard, 1.), color::linear_to_srgb(0.75));
+    // Values above 1 stay distinct and below white.
+    let (two, four, many) = (resolved(2., ToneMap::Reinhard, 1.),
+                             resolved(4., ToneMap::Reinhard, 1.),
+                             resolved(20., ToneMap::Reinhard, 1.));
+    assert!(two < four && four < many && many < 255,
+            "{} {} {}",
+            two,
+            four,
+            many);
+}
+
+#[test]
+fn aces_filmic() {
+    assert_eq!(resolved(0., ToneMap::AcesFilmic, 1.), 0);
+    // The toe darkens near black, the shoulder rolls highlights off to white.
+    assert!(resolved(0.01, ToneMap::AcesFilmic, 1.) < color::linear_to_srgb(0.01));
+    let one = resolved(1., ToneMap::AcesFilmic, 1.);
+    let four = resolved(4., ToneMap::AcesFilmic, 1.);
+    assert!(one < four && four < 255, "{} {}", one, four);
+    assert_eq!(resolved(100., ToneMap::AcesFilmic, 1.), 255);
+}
+
+#[test]
+fn exposure() {
+    for &tm in &[ToneMap::Clamp, ToneMap::Reinhard, ToneMap::AcesFilmic] {
+        assert_eq!(resolved(0.25, tm, 2.), resolved(0.5, tm, 1.), "{:?}", tm);
+        assert_eq!(resolved(8., tm, 0.25), resolved(2., tm, 1.), "{:?}", tm);
+    }
+    // Exposing down brings light above 1 back into range.
+    assert_eq!(resolved(4., ToneMap::Clamp, 0.125), color::linear_to_srgb(0.5));
+}
+
+#[test]
+fn srgb_round_trip() {
+    let c = rustyrenderer::draw::RGB {
+        r: 0,
+        g: 128,
+        b: 255,
+    };
+    let got = Rgba::from_srgb(c).to_srgb();
+    assert_eq!((got.r, got.g, got.b), (0, 128, 255));
+}
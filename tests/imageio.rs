@@ -0,0 +1,155 @@
+extern crate imagefmt;
+extern crate rustyrenderer;
+
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use rustyrenderer::draw;
+use rustyrenderer::hdr;
+
+fn temp(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("rustyrenderer-{}-{}", std::process::id(), name))
+}
+
+// Checks 8-bit images round trip through PPM, and are written top row first with y up in memory.
+#[test]
+fn ppm_round_trip() {
+    let mut im = draw::Image::new(3, 2);
+    for (i, v) in im.buf.iter_mut().enumerate() {
+        *v = (i * 13) as u8;
+    }
+    let path = temp("round-trip.ppm");
+    im.save(&path).unwrap();
+
+    let mut data = Vec::new();
+    fs::File::open(&path).unwrap().read_to_end(&mut data).unwrap();
+    let header = b"P6\n3 2\n255\n";
+    assert_eq!(&data[..header.len()], header);
+    // The file's first row is the image's top row, y = 1.
+    assert_eq!(&data[header.len()..header.len() + 9], &im.buf[9..18]);
+
+    let back = draw::Image::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!((back.w, back.h), (3, 2));
+    assert_eq!(back.buf, im.buf);
+}
+
+// Checks float images round trip through PFM and EXR, keeping values above 1, and alpha for EXR.
+#[test]
+fn float_round_trip() {
+    let mut im = hdr::HdrImage::new(4, 3);
+    for (i, v) in im.buf.iter_mut().enumerate() {
+        *v = i as f32 * 0.37 - 1.5;
+    }
+    for &ext in &["pfm", "exr", "EXR"] {
+        let path = temp(&format!("round-trip.{}", ext));
+        im.save(&path).unwrap();
+        let back = hdr::HdrImage::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((back.w, back.h), (4, 3));
+        let keeps_alpha = ext != "pfm";
+        for (i, (&a, &b)) in im.buf.iter().zip(back.buf.iter()).enumerate() {
+            let want = if i % 4 == 3 && !keeps_alpha { 1. } else { a };
+            assert_eq!(b, want, "{} value {}", ext, i);
+        }
+    }
+
+    // 8-bit formats need tone mapping first, and unknown extensions are rejected.
+    assert!(im.save(temp("hdr.png")).is_err());
+    assert!(draw::Image::new(1, 1).save(temp("image.xyz")).is_err());
+}
+
+// Checks 8-bit images round trip through the formats written with imagefmt, and are written top
+// row first with y up in memory.
+#[test]
+fn imagefmt_round_trip() {
+    let mut im = draw::Image::new(3, 2);
+    for (i, v) in im.buf.iter_mut().enumerate() {
+        *v = (i * 13) as u8;
+    }
+    for &ext in &["png", "tga", "bmp"] {
+        let path = temp(&format!("round-trip.{}", ext));
+        im.save(&path).unwrap();
+
+        // The file's first row is the image's top row, y = 1.
+        let file = imagefmt::read(&path, imagefmt::ColFmt::RGB).unwrap();
+        assert_eq!((file.w, file.h), (3, 2), "{}", ext);
+        assert_eq!(&file.buf[..9], &im.buf[9..18], "{}", ext);
+
+        let back = draw::Image::load(&path).unwrap();
+        assert_eq!((back.w, back.h), (3, 2), "{}", ext);
+        assert_eq!(back.buf, im.buf, "{}", ext);
+
+        // Float loads flip the same way and decode to linear.
+        let linear = hdr::HdrImage::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        for y in 0..2 {
+            for x in 0..3 {
+                let (got, want) = (linear.get(x, y), hdr::Rgba::from_srgb(im.get(x, y)));
+                assert_eq!((got.r, got.g, got.b, got.a),
+                           (want.r, want.g, want.b, 1.),
+                           "{} {},{}",
+                           ext,
+                           x,
+                           y);
+            }
+        }
+    }
+}
+
+// load_bytes writes data to a file named name and loads it as a float image.
+fn load_bytes(name: &str, data: &[u8]) -> Result<(usize, usize), String> {
+    let path = temp(name);
+    fs::File::create(&path).unwrap().write_all(data).unwrap();
+    let res = hdr::HdrImage::load(&path);
+    fs::remove_file(&path).unwrap();
+    res.map(|im| (im.w, im.h)).map_err(|e| e.to_string())
+}
+
+// Checks malformed headers are errors rather than panics or huge allocations.
+#[test]
+fn malformed_headers() {
+    let cases: Vec<(&str, Vec<u8>)> =
+        vec![("huge.ppm", b"P6\n100000 100000\n255\n\x01\x02\x03".to_vec()),
+             ("overflow.ppm", b"P6\n18446744073709551615 3\n255\n\x01\x02\x03".to_vec()),
+             ("huge-ascii.ppm", b"P3\n100000 100000\n255\n1 2 3".to_vec()),
+             ("short.ppm", b"P6\n2 2\n255\n\x01\x02\x03".to_vec()),
+             ("huge.pfm", b"PF\n100000 100000\n-1.0\n\0\0\0\0".to_vec()),
+             ("overflow.pfm", b"PF\n18446744073709551615 3\n-1.0\n\0\0\0\0".to_vec())];
+    for &(name, ref data) in &cases {
+        assert!(load_bytes(name, data).is_err(), "{}", name);
+    }
+
+    // A valid EXR to patch, its dataWindow corners follow the attribute's name, type and size.
+    let path = temp("valid.exr");
+    hdr::HdrImage::new(2, 2).save(&path).unwrap();
+    let mut exr = Vec::new();
+    fs::File::open(&path).unwrap().read_to_end(&mut exr).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(load_bytes("valid.exr", &exr), Ok((2, 2)));
+    let attr = b"dataWindow\0box2i\0";
+    let window = exr.windows(attr.len()).position(|w| w == &attr[..]).unwrap() + attr.len() + 4;
+    let with_window = |corners: [i32; 4]| {
+        let mut exr = exr.clone();
+        for (i, c) in corners.iter().enumerate() {
+            let v = *c as u32;
+            let off = window + i * 4;
+            exr[off..off + 4].copy_from_slice(&[v as u8, (v >> 8) as u8, (v >> 16) as u8,
+                                                (v >> 24) as u8]);
+        }
+        exr
+    };
+    // Inverted, enormous and overflowing windows.
+    for corners in &[[0, 0, -2, 1], [0, 0, 100000, 100000], [i32::MIN, 0, i32::MAX, 1]] {
+        assert!(load_bytes("window.exr", &with_window(*corners)).is_err(),
+                "{:?}",
+                corners);
+    }
+
+    // A header ending in a compression attribute with no value.
+    let mut exr = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+    exr.extend_from_slice(b"compression\0compression\0\0\0\0\0");
+    assert!(load_bytes("compression.exr", &exr).is_err());
+}
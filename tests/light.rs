@@ -0,0 +1,90 @@
+extern crate math;
+extern crate rustyrenderer;
+
+mod common;
+
+use math::Vec3f;
+use rustyrenderer::{light, render, shader, shadow};
+
+fn v(x: f32, y: f32, z: f32) -> Vec3f {
+    Vec3f { x: x, y: y, z: z }
+}
+
+// Checks lights don't light the point they're at, rather than lighting it with NaN.
+#[test]
+fn no_light_at_light_position() {
+    let p = v(1., 2., 3.);
+    assert!(light::Light::point(p).incident(p).is_none());
+    assert!(light::Light::spot(p, v(0., 0., -1.), 0.3, 0.5).incident(p).is_none());
+    let near = light::Light::point(p).incident(v(1., 2., 2.)).unwrap();
+    assert!(near.radiance.x.is_finite() && near.dir.z == 1.);
+}
+
+// Checks a spot light's shadow map covers its cone when it isn't aimed at the scene center.
+#[test]
+fn spot_shadow_follows_axis() {
+    // A small square between the light and the ground below it.
+    let occluder = common::object("v 2.7 -0.3 1.5\nv 3.3 -0.3 1.5\nv 3.3 0.3 1.5\nv 2.7 0.3 1.5
+vt 0 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n");
+    let mut world = shader::World::new();
+    world.clear_lights();
+    let spot = world.add_light(light::Light::spot(v(3., 0., 3.), v(0., 0., -1.), 0.5, 0.6));
+    let map = shadow::ShadowMap::new(&render::Renderer::new(),
+                                     &world,
+                                     spot,
+                                     &occluder,
+                                     Vec3f::zero(),
+                                     128);
+    assert_eq!(map.visibility(v(3., 0., 0.)), 0.);
+    assert_eq!(map.visibility(v(4.5, 0., 0.)), 1.);
+    assert_eq!(map.visibility(v(3., 1., 0.)), 1.);
+}
+
+// Checks a new World is lit by one white light shining from -z, as before it had a list of lights.
+#[test]
+fn world_default_light() {
+    let world = shader::World::new();
+    assert_eq!(world.lights().len(), 1);
+    let inc = world.lights()[0].incident(v(5., -2., 1.)).unwrap();
+    assert_eq!((inc.dir.x, inc.dir.y, inc.dir.z), (0., 0., -1.));
+    assert_eq!((inc.radiance.x, inc.radiance.y, inc.radiance.z), (1., 1., 1.));
+}
+
+// Checks a spot light's cone fades from full strength at the inner angle to nothing at the outer,
+// whichever order the angles are given in, and is never outside 0 to 1.
+#[test]
+fn spot_cone_in_range() {
+    // Radiance of spot, one unit above the ground, at the point on the ground at angle a from its
+    // axis, without falloff with distance.
+    let at = |inner: f32, outer: f32, a: f32| {
+        let mut spot = light::Light::spot(v(0., 0., 1.), v(0., 0., -1.), inner, outer);
+        if let light::Source::Spot { ref mut attenuation, .. } = spot.source {
+            *attenuation = light::Attenuation::new(1., 0., 0.);
+        }
+        spot.incident(v(a.tan(), 0., 0.)).map_or(0., |inc| inc.radiance.x)
+    };
+    for &(inner, outer) in &[(0.3, 0.6), (0.6, 0.3)] {
+        assert_eq!(at(inner, outer, 0.), 1.);
+        assert_eq!(at(inner, outer, 0.25), 1.);
+        let mid = at(inner, outer, 0.45);
+        assert!(mid > 0. && mid < 1., "{} {}: {}", inner, outer, mid);
+        assert_eq!(at(inner, outer, 0.7), 0.);
+    }
+
+    // Built directly the angles aren't swapped, but the cone still can't go negative.
+    let spot = light::Light {
+        source: light::Source::Spot {
+            pos: v(0., 0., 1.),
+            dir: v(0., 0., -1.),
+            inner: 0.6,
+            outer: 0.3,
+            attenuation: light::Attenuation::new(1., 0., 0.),
+        },
+        ..light::Light::directional(v(0., 0., 1.))
+    };
+    for i in 0..30 {
+        let x = i as f32 * 0.05;
+        let r = spot.incident(v(x, 0., 0.)).map_or(0., |inc| inc.radiance.x);
+        assert!(r >= 0. && r <= 1., "{}: {}", x, r);
+    }
+}
@@ -0,0 +1,37 @@
+extern crate rustyrenderer;
+
+use rustyrenderer::draw;
+use rustyrenderer::hdr::Rgba;
+use rustyrenderer::oit::OitBuffer;
+
+// Checks compositing doesn't depend on the order fragments are added in.
+#[test]
+fn order_independent() {
+    let red = Rgba {
+        r: 1.,
+        g: 0.,
+        b: 0.,
+        a: 0.5,
+    };
+    let blue = Rgba {
+        r: 0.,
+        g: 0.,
+        b: 1.,
+        a: 0.3,
+    };
+    let mut results = Vec::new();
+    for &(first, second) in &[((red, 100.), (blue, 200.)), ((blue, 200.), (red, 100.))] {
+        let mut oit = OitBuffer::new(1, 1);
+        oit.add(0, 0, first.1, first.0);
+        oit.add(0, 0, second.1, second.0);
+        let mut im = draw::Image::new(1, 1);
+        im.set(0, 0, draw::RGB { r: 0, g: 255, b: 0 });
+        oit.composite(&mut im);
+        let c = im.get(0, 0);
+        results.push((c.r, c.g, c.b));
+    }
+    assert_eq!(results[0], results[1]);
+    // 35% of the green background shows through both layers.
+    let (_, g, _) = results[0];
+    assert!(g > 140 && g < 170, "green {}", g);
+}
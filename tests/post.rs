@@ -0,0 +1,109 @@
+extern crate rustyrenderer;
+
+use rustyrenderer::{draw, post, shader};
+
+const SIZE: usize = 64;
+
+// ssao runs Ssao, outputting only the ambient term, on a depth buffer with depth z(x, y), in
+// pixels toward the camera, at each pixel and returns the result.
+fn ssao<F: Fn(usize, usize) -> f32>(samples: usize, z: F) -> draw::Image {
+    let mut world = shader::World::new();
+    world.set_viewport(0, 0, SIZE, SIZE);
+    // The viewport maps depth to DEPTH_RESOLUTION across the width.
+    let pixel = shader::DEPTH_RESOLUTION / SIZE as f32;
+    let mut z_buffer = draw::DepthBuffer::new(SIZE, SIZE);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            z_buffer.set(x, y, shader::DEPTH_RESOLUTION / 2. + z(x, y) * pixel);
+        }
+    }
+    let mut ssao = post::Ssao::new();
+    ssao.set_samples(samples);
+    ssao.set_output(post::SsaoOutput::AoOnly);
+    let mut im = draw::Image::new(SIZE, SIZE);
+    ssao.apply(&world, &mut im, &z_buffer);
+    im
+}
+
+// Checks flat surfaces, facing the camera or not, aren't occluded, even sampled once or with the
+// sample count set to zero.
+#[test]
+fn ssao_flat_unoccluded() {
+    for &samples in &[0, 1, 16] {
+        let facing = ssao(samples, |_, _| 0.);
+        let sloped = ssao(samples, |x, y| x as f32 * 0.25 + y as f32 * 0.1);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                assert_eq!(facing.get(x, y).r, 255, "{} samples pixel {},{}", samples, x, y);
+                assert!(sloped.get(x, y).r > 230, "{} samples pixel {},{}", samples, x, y);
+            }
+        }
+    }
+}
+
+// Checks the inner corner where a wall meets a floor is darker than the open floor.
+#[test]
+fn ssao_corner_darker() {
+    // A steep wall rising toward the camera left of x = 32 and a floor right of it.
+    let im = ssao(16, |x, _| if x < 32 { (32 - x) as f32 * 4. } else { 0. });
+    let (corner, open) = (im.get(33, 32).r as i32, im.get(56, 32).r as i32);
+    assert!(corner + 20 < open, "corner {} open {}", corner, open);
+}
+
+// Checks Ssao refuses an image and depth buffer of different sizes.
+#[test]
+#[should_panic]
+fn ssao_size_mismatch() {
+    let mut world = shader::World::new();
+    world.set_viewport(0, 0, SIZE, SIZE);
+    let mut im = draw::Image::new(SIZE, SIZE / 2);
+    post::Ssao::new().apply(&world, &mut im, &draw::DepthBuffer::new(SIZE, SIZE));
+}
+
+// Checks Outline marks a step in depth and a crease between normals, but not a flat, evenly lit
+// region.
+#[test]
+fn outline_edges() {
+    let mut world = shader::World::new();
+    world.set_viewport(0, 0, SIZE, SIZE);
+    let pixel = shader::DEPTH_RESOLUTION / SIZE as f32;
+    let mut z_buffer = draw::DepthBuffer::new(SIZE, SIZE);
+    // Normals facing the camera, encoded as shader::NormalShader does, in the upper half, and
+    // turned 60 degrees about y below.
+    let mut normals = draw::Image::new(SIZE, SIZE);
+    let white = draw::RGB {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+    let mut im = draw::Image::new(SIZE, SIZE);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            // A step 20 pixels toward the camera right of x = 48.
+            let z = if x >= 48 { 20. } else { 0. };
+            z_buffer.set(x, y, shader::DEPTH_RESOLUTION / 2. + z * pixel);
+            let n = if y >= SIZE / 2 {
+                draw::RGB {
+                    r: 128,
+                    g: 128,
+                    b: 255,
+                }
+            } else {
+                draw::RGB {
+                    r: 238,
+                    g: 128,
+                    b: 191,
+                }
+            };
+            normals.set(x, y, n);
+            im.set(x, y, white);
+        }
+    }
+    post::Outline::new().apply(&world, &mut im, &z_buffer, &normals);
+    let black = |x: usize, y: usize| im.get(x, y).r == 0;
+    assert!(black(47, 48) || black(48, 48), "depth step");
+    assert!(black(16, SIZE / 2 - 1) || black(16, SIZE / 2), "crease");
+    for &(x, y) in &[(16, 48), (16, 16), (56, 48)] {
+        assert!(!black(x, y), "flat pixel {},{}", x, y);
+    }
+}
@@ -0,0 +1,181 @@
+extern crate math;
+extern crate rustyrenderer;
+
+use math::Vec3f;
+use rustyrenderer::raster;
+
+// Renders a quad covering the whole image, tessellated into a grid of triangles with jittered
+// interior vertices and mixed windings, and checks every pixel is written exactly once.
+#[test]
+fn tessellated_quad_is_watertight() {
+    let size = 64;
+    let cells = 8;
+    let step = size as f32 / cells as f32;
+
+    // Grid vertices, the border stays on the quad's edges, interior vertices move by quarter pixels
+    // so many shared edges pass exactly through pixel centers.
+    let mut grid = Vec::new();
+    for j in 0..cells + 1 {
+        for i in 0..cells + 1 {
+            let interior = i > 0 && i < cells && j > 0 && j < cells;
+            let (dx, dy) = if interior {
+                (((i * 7 + j * 13) % 13) as f32 * 0.25 - 1.5,
+                 ((i * 5 + j * 3) % 13) as f32 * 0.25 - 1.5)
+            } else {
+                (0., 0.)
+            };
+            grid.push(Vec3f {
+                x: i as f32 * step + dx,
+                y: j as f32 * step + dy,
+                z: 0.,
+            });
+        }
+    }
+
+    let mut counts = vec![0; size * size];
+    for j in 0..cells {
+        for i in 0..cells {
+            let v00 = grid[i + j * (cells + 1)];
+            let v10 = grid[i + 1 + j * (cells + 1)];
+            let v01 = grid[i + (j + 1) * (cells + 1)];
+            let v11 = grid[i + 1 + (j + 1) * (cells + 1)];
+            // Alternate the diagonal and the winding from cell to cell.
+            let tris = if (i + j) % 2 == 0 {
+                [[v00, v10, v11], [v00, v01, v11]]
+            } else {
+                [[v00, v10, v01], [v10, v01, v11]]
+            };
+            for tri in &tris {
+                raster::rasterize(tri, size, size, |x, y, _bc| counts[x + y * size] += 1);
+            }
+        }
+    }
+
+    for y in 0..size {
+        for x in 0..size {
+            assert_eq!(counts[x + y * size], 1, "pixel {},{}", x, y);
+        }
+    }
+}
+
+// Checks the blocked, incremental rasterizer produces the same fragments as the reference one,
+// including triangles clipped by the image edges and thin slivers.
+#[test]
+fn incremental_matches_reference() {
+    let size = 50;
+    let mut tris = Vec::new();
+    for i in 0..200 {
+        let f = i as f32;
+        let v = |a: f32, b: f32| {
+            Vec3f {
+                x: (f * a).sin() * 40. + 25.,
+                y: (f * b).cos() * 40. + 25.,
+                z: 0.,
+            }
+        };
+        tris.push([v(1.3, 0.7), v(2.9, 1.1), v(0.3, 3.7)]);
+    }
+    tris.push([Vec3f { x: 0.25, y: 0.5, z: 0. },
+               Vec3f { x: 49.75, y: 1.5, z: 0. },
+               Vec3f { x: 49.5, y: 1.25, z: 0. }]);
+
+    for tri in &tris {
+        let mut want = Vec::new();
+        raster::rasterize_reference(tri, size, size, |x, y, bc| want.push((x, y, bc)));
+        let mut got = Vec::new();
+        raster::rasterize(tri, size, size, |x, y, bc| got.push((x, y, bc)));
+        got.sort_by_key(|&(x, y, _)| (y, x));
+
+        assert_eq!(got.len(), want.len());
+        for (g, w) in got.iter().zip(want.iter()) {
+            assert_eq!((g.0, g.1), (w.0, w.1));
+            assert_eq!((g.2.x, g.2.y, g.2.z), (w.2.x, w.2.y, w.2.z));
+        }
+    }
+}
+
+// Checks derivatives matches the difference in barycentric coordinates between neighboring
+// fragments, for both windings.
+#[test]
+fn derivatives_match_neighbors() {
+    let size = 32;
+    let a = Vec3f { x: 2.5, y: 3., z: 0. };
+    let b = Vec3f { x: 29., y: 6.5, z: 0. };
+    let c = Vec3f { x: 9., y: 28., z: 0. };
+    for tri in &[[a, b, c], [a, c, b]] {
+        let (dx, dy) = raster::derivatives(tri).unwrap();
+        let mut bcs = vec![None; size * size];
+        raster::rasterize(tri, size, size, |x, y, bc| bcs[x + y * size] = Some(bc));
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let p = match bcs[x + y * size] {
+                    Some(p) => p,
+                    None => continue,
+                };
+                for &(n, d) in &[(bcs[x + 1 + y * size], dx), (bcs[x + (y + 1) * size], dy)] {
+                    if let Some(n) = n {
+                        let diff = n - p;
+                        assert!((diff.x - d.x).abs() < 1e-4 && (diff.y - d.y).abs() < 1e-4 &&
+                                (diff.z - d.z).abs() < 1e-4,
+                                "pixel {},{}",
+                                x,
+                                y);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Checks every sample of a tessellated quad is covered exactly once at each MSAA sample count, and
+// that one sample per pixel covers the same pixels as rasterize.
+#[test]
+fn multisample_is_watertight() {
+    let size = 32;
+    let step = 8.;
+    let mut tris = Vec::new();
+    for j in 0..4 {
+        for i in 0..4 {
+            // Skew the interior of the grid so edges run at odd angles through samples.
+            let v = |i: usize, j: usize| {
+                let interior = i > 0 && i < 4 && j > 0 && j < 4;
+                let d = if interior { ((i * 3 + j * 5) % 7) as f32 * 0.375 - 1. } else { 0. };
+                Vec3f {
+                    x: i as f32 * step + d,
+                    y: j as f32 * step - d,
+                    z: 0.,
+                }
+            };
+            tris.push([v(i, j), v(i + 1, j), v(i + 1, j + 1)]);
+            tris.push([v(i, j), v(i, j + 1), v(i + 1, j + 1)]);
+        }
+    }
+    for &samples in &[1, 2, 4, 8] {
+        let pattern = raster::sample_pattern(samples);
+        let mut counts = vec![0; size * size * samples];
+        for tri in &tris {
+            raster::rasterize_samples(tri, (0, 0, size, size), pattern, |x, y, mask, _bc| {
+                for s in 0..samples {
+                    if mask & (1 << s) != 0 {
+                        counts[(x + y * size) * samples + s] += 1;
+                    }
+                }
+            });
+        }
+        for (i, &c) in counts.iter().enumerate() {
+            assert_eq!(c, 1, "{}x sample {} of pixel {}", samples, i % samples, i / samples);
+        }
+    }
+
+    for tri in &tris {
+        let mut want = Vec::new();
+        raster::rasterize(tri, size, size, |x, y, _bc| want.push((x, y)));
+        let mut got = Vec::new();
+        raster::rasterize_samples(tri,
+                                  (0, 0, size, size),
+                                  raster::sample_pattern(1),
+                                  |x, y, _mask, _bc| got.push((x, y)));
+        want.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(got, want);
+    }
+}
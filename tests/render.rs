@@ -0,0 +1,181 @@
+extern crate math;
+extern crate rustyrenderer;
+
+mod common;
+
+use math::Vec3f;
+use rustyrenderer::{draw, light, render, shader};
+
+// Checks rendering on several threads gives exactly the same colors and depths as one thread, for
+// tile sizes that cut through many faces.
+#[test]
+fn threads_match_single_threaded() {
+    // Two interleaved, jittered grids at different depths so faces overlap and straddle tiles.
+    let mut src = String::new();
+    let cells = 6;
+    for layer in 0..2 {
+        for j in 0..cells + 1 {
+            for i in 0..cells + 1 {
+                let f = (i * 7 + j * 11 + layer * 5) as f32;
+                src += &format!("v {} {} {}\n",
+                                i as f32 / cells as f32 * 1.8 - 0.9 + layer as f32 * 0.07,
+                                j as f32 / cells as f32 * 1.8 - 0.9 - layer as f32 * 0.05,
+                                f.sin() * 0.3 + layer as f32 * 0.1 - 0.05);
+                src += &format!("vt {} {} 0\n", i as f32 / cells as f32, j as f32 / cells as f32);
+                src += &format!("vn {} {} 1\n", f.cos() * 0.3, (f * 1.7).sin() * 0.3);
+            }
+        }
+    }
+    let per_layer = (cells + 1) * (cells + 1);
+    for layer in 0..2 {
+        for j in 0..cells {
+            for i in 0..cells {
+                // Indices are 1-based.
+                let v = |i: usize, j: usize| layer * per_layer + i + j * (cells + 1) + 1;
+                let (a, b, c, d) = (v(i, j), v(i + 1, j), v(i + 1, j + 1), v(i, j + 1));
+                src += &format!("f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}\n", a, b, c);
+                src += &format!("f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}\n", a, c, d);
+            }
+        }
+    }
+    let obj = common::object(&src);
+
+    let (w, h) = (97, 83);
+    let mut world = shader::World::new();
+    world.set_viewport(0, 0, w, h);
+    world.set_cull_mode(shader::CullMode::None);
+    world.clear_lights();
+    world.add_light(light::Light::directional(Vec3f { x: 1., y: 1., z: 1. }));
+    let up = Vec3f { x: 0., y: 1., z: 0. };
+    world.look_at(Vec3f { x: 0.3, y: 0.2, z: 3. }, Vec3f::zero(), up);
+    let shdr = shader::PhongShader::new(&obj);
+
+    let render = |threads: usize, tile_size: usize| {
+        let mut renderer = render::Renderer::new();
+        renderer.set_threads(threads);
+        renderer.set_tile_size(tile_size);
+        let mut im = draw::Image::new(w, h);
+        let mut z_buffer = draw::DepthBuffer::new(w, h);
+        renderer.draw(&world, &obj, &shdr, &mut im, &mut z_buffer);
+        let depth: Vec<u32> = (0..w * h).map(|i| z_buffer.get(i % w, i / w).to_bits()).collect();
+        (im.buf, depth)
+    };
+    let (want_im, want_depth) = render(1, 64);
+    assert!(want_im.iter().any(|&v| v != 0), "nothing rendered");
+    for &threads in &[2, 3, 8] {
+        for &tile_size in &[1, 7, 16, 64, 200] {
+            let (im, depth) = render(threads, tile_size);
+            assert!(im == want_im, "{} threads, {} pixel tiles: colors differ", threads, tile_size);
+            assert!(depth == want_depth,
+                    "{} threads, {} pixel tiles: depths differ",
+                    threads,
+                    tile_size);
+        }
+    }
+}
+
+// A counter-clockwise triangle on the left and a clockwise one on the right, as seen from +z,
+// facing the camera.
+const WINDINGS: &'static str = "v -0.9 -0.5 0\nv -0.1 -0.5 0\nv -0.5 0.5 0
+v 0.1 -0.5 0\nv 0.5 0.5 0\nv 0.9 -0.5 0\nvt 0 0 0\nvn 0 0 1
+f 1/1/1 2/1/1 3/1/1\nf 4/1/1 5/1/1 6/1/1\n";
+
+// Checks which windings each cull mode draws, for both front face windings, and that the light
+// has nothing to do with it.
+#[test]
+fn cull_modes() {
+    let obj = common::object(WINDINGS);
+    let size = 32;
+    // Which of the counter-clockwise and clockwise triangles reach the image.
+    let drawn = |cull_mode: shader::CullMode, front_face: shader::Winding| {
+        let mut world = shader::World::new();
+        world.set_viewport(0, 0, size, size);
+        world.set_cull_mode(cull_mode);
+        world.set_front_face(front_face);
+        // Looking from +z with the default light shining from -z, the triangles are lit from
+        // behind.
+        world.look_at(Vec3f { x: 0., y: 0., z: 3. },
+                      Vec3f::zero(),
+                      Vec3f { x: 0., y: 1., z: 0. });
+        let mut im = draw::Image::new(size, size);
+        let mut z_buffer = draw::DepthBuffer::new(size, size);
+        render::Renderer::new().draw(&world,
+                                     &obj,
+                                     &shader::GouraudShader::new(&obj),
+                                     &mut im,
+                                     &mut z_buffer);
+        // The triangles' centroids.
+        let hit = |x: usize| z_buffer.get(x, size / 2 - 3) != std::f32::MIN;
+        (hit(size / 4), hit(3 * size / 4))
+    };
+    let (ccw, cw) = (shader::Winding::CCW, shader::Winding::CW);
+    let cases = [(shader::CullMode::None, ccw, (true, true)),
+                 (shader::CullMode::None, cw, (true, true)),
+                 (shader::CullMode::Back, ccw, (true, false)),
+                 (shader::CullMode::Back, cw, (false, true)),
+                 (shader::CullMode::Front, ccw, (false, true)),
+                 (shader::CullMode::Front, cw, (true, false))];
+    for &(cull_mode, front_face, want) in &cases {
+        assert_eq!(drawn(cull_mode, front_face), want, "{:?} {:?}", cull_mode, front_face);
+    }
+}
+
+// A quad facing the camera in front of the lower left corner of a larger quad behind it, both
+// given as single faces, with projection left as the identity so screen space is the viewport.
+const QUADS: &'static str = "v -0.5 -0.5 0.5\nv 0.5 -0.5 0.5\nv 0.5 0.5 0.5\nv -0.5 0.5 0.5
+v -0.25 -0.25 -0.5\nv 0.75 -0.25 -0.5\nv 0.75 0.75 -0.5\nv -0.25 0.75 -0.5\nvt 0 0 0\nvn 0 0 1
+f 1/1/1 2/1/1 3/1/1 4/1/1\nf 5/1/1 6/1/1 7/1/1 8/1/1\n";
+
+// Checks wireframes draw hidden edges, hidden line and overlay drawing don't, and only original
+// polygon edges are drawn when asked.
+#[test]
+fn wireframes() {
+    let obj = common::object(QUADS);
+    let size = 32;
+    let mut world = shader::World::new();
+    world.set_viewport(0, 0, size, size);
+    world.clear_lights();
+    world.add_light(light::Light::directional(Vec3f { x: 0., y: 0., z: 1. }));
+    let red = draw::RGB { r: 255, g: 0, b: 0 };
+    let renderer = render::Renderer::new();
+    let is_red = |im: &draw::Image, x: usize, y: usize| {
+        let c = im.get(x, y);
+        (c.r, c.g, c.b) == (255, 0, 0)
+    };
+    // Pixels on the left edge of the quad in front, the left edge of the one behind where it's
+    // hidden, the right edge of the one behind, and the diagonal splitting the quad in front.
+    let (front, hidden, back, diagonal) = ((8, 16), (12, 16), (28, 16), (10, 10));
+
+    let mut style = render::Wireframe::new(red);
+    let mut im = draw::Image::new(size, size);
+    renderer.draw_wireframe(&world, &obj, &style, &mut im);
+    for &(x, y) in &[front, hidden, back, diagonal] {
+        assert!(is_red(&im, x, y), "wireframe pixel {},{}", x, y);
+    }
+
+    let mut im = draw::Image::new(size, size);
+    let mut z_buffer = draw::DepthBuffer::new(size, size);
+    renderer.draw_hidden_line(&world, &obj, &style, &mut im, &mut z_buffer);
+    for &(x, y) in &[front, back, diagonal] {
+        assert!(is_red(&im, x, y), "hidden line pixel {},{}", x, y);
+    }
+    assert_eq!(im.get(hidden.0, hidden.1).r, 0);
+
+    style.polygon_edges_only = true;
+    let mut im = draw::Image::new(size, size);
+    let mut z_buffer = draw::DepthBuffer::new(size, size);
+    let shdr = shader::GouraudShader::new(&obj);
+    renderer.draw_overlay(&world, &obj, &shdr, &style, &mut im, &mut z_buffer);
+    for &(x, y) in &[front, back] {
+        assert!(is_red(&im, x, y), "overlay pixel {},{}", x, y);
+    }
+    for &(x, y) in &[hidden, diagonal] {
+        let c = im.get(x, y);
+        assert!(c.r > 0 && c.r == c.g, "overlay pixel {},{} {:?}", x, y, c);
+    }
+
+    let mut im = draw::Image::new(size, size);
+    renderer.draw_wireframe(&world, &obj, &style, &mut im);
+    assert!(!is_red(&im, diagonal.0, diagonal.1));
+    assert!(is_red(&im, hidden.0, hidden.1));
+}
@@ -0,0 +1,159 @@
+extern crate rustyrenderer;
+
+use rustyrenderer::{color, draw};
+use rustyrenderer::resample::{self, Filter};
+
+// Checks every filter keeps a flat color unchanged, including at the image edges, and halves the
+// size.
+#[test]
+fn flat_color_is_preserved() {
+    let c = draw::RGB {
+        r: 200,
+        g: 90,
+        b: 17,
+    };
+    let mut src = draw::Image::new(24, 16);
+    for y in 0..src.h {
+        for x in 0..src.w {
+            src.set(x, y, c);
+        }
+    }
+    for &filter in &[Filter::Box, Filter::Tent, Filter::Lanczos, Filter::Mitchell] {
+        let im = resample::downsample(&src, 4, filter);
+        assert_eq!((im.w, im.h), (6, 4));
+        for y in 0..im.h {
+            for x in 0..im.w {
+                let got = im.get(x, y);
+                assert_eq!((got.r, got.g, got.b), (c.r, c.g, c.b), "{:?} {},{}", filter, x, y);
+            }
+        }
+    }
+}
+
+const FILTERS: [Filter; 4] = [Filter::Box, Filter::Tent, Filter::Lanczos, Filter::Mitchell];
+
+// edge returns a w x h image of grey level lo left of column w / 2 and hi from it on.
+fn edge(w: usize, h: usize, lo: u8, hi: u8) -> draw::Image {
+    let mut im = draw::Image::new(w, h);
+    for y in 0..h {
+        for x in 0..w {
+            let v = if x < w / 2 { lo } else { hi };
+            im.set(x,
+                   y,
+                   draw::RGB {
+                       r: v,
+                       g: v,
+                       b: v,
+                   });
+        }
+    }
+    im
+}
+
+// row returns the red channel of the middle row of im.
+fn row(im: &draw::Image) -> Vec<u8> {
+    (0..im.w).map(|x| im.get(x, im.h / 2).r).collect()
+}
+
+// Checks the filters against values computed from their definitions.
+#[test]
+fn weights() {
+    let cases = [(Filter::Box, 0., 1.),
+                 (Filter::Box, 0.25, 1.),
+                 (Filter::Box, 0.5, 0.),
+                 (Filter::Tent, 0.25, 0.75),
+                 (Filter::Tent, -0.5, 0.5),
+                 (Filter::Tent, 1., 0.),
+                 // 3 sin(pi x) sin(pi x / 3) / (pi x)^2.
+                 (Filter::Lanczos, 0., 1.),
+                 (Filter::Lanczos, 0.5, 0.607927),
+                 (Filter::Lanczos, 1., 0.),
+                 (Filter::Lanczos, -1.5, -0.135095),
+                 (Filter::Lanczos, 3., 0.),
+                 // (6 - 2B) / 6, (B + 6C) / 6 at 1, negative lobe between 1 and 2.
+                 (Filter::Mitchell, 0., 0.888889),
+                 (Filter::Mitchell, 1., 0.055556),
+                 (Filter::Mitchell, 1.5, -0.034722),
+                 (Filter::Mitchell, 2., 0.)];
+    for &(filter, x, want) in &cases {
+        let got = filter.weight(x);
+        assert!((got - want).abs() < 1e-5,
+                "{:?} weight({}) = {}, want {}",
+                filter,
+                x,
+                got,
+                want);
+    }
+}
+
+// Checks the box filter averages in linear light, a black and white checkerboard becomes the grey
+// of half intensity rather than sRGB 128, and leaves an edge on an output pixel boundary sharp.
+#[test]
+fn box_is_linear_mean() {
+    let mut src = draw::Image::new(16, 16);
+    for y in 0..src.h {
+        for x in 0..src.w {
+            src.set(x,
+                    y,
+                    if (x + y) % 2 == 0 {
+                        color::WHITE
+                    } else {
+                        draw::RGB { r: 0, g: 0, b: 0 }
+                    });
+        }
+    }
+    let grey = color::linear_to_srgb(0.5);
+    for &factor in &[2, 4] {
+        let im = resample::downsample(&src, factor, Filter::Box);
+        assert!(im.buf.iter().all(|&c| c == grey), "{} {:?}", factor, im.buf);
+    }
+
+    let im = resample::downsample(&edge(48, 8, 64, 192), 4, Filter::Box);
+    assert_eq!(row(&im), vec![64, 64, 64, 64, 64, 64, 192, 192, 192, 192, 192, 192]);
+}
+
+// Checks mirroring the source mirrors the output, every filter weighs both sides alike.
+#[test]
+fn symmetric() {
+    let src = edge(48, 8, 30, 220);
+    let mut mirrored = draw::Image::new(src.w, src.h);
+    for y in 0..src.h {
+        for x in 0..src.w {
+            mirrored.set(src.w - 1 - x, y, src.get(x, y));
+        }
+    }
+    for &filter in &FILTERS {
+        let a = row(&resample::downsample(&src, 4, filter));
+        let mut b = row(&resample::downsample(&mirrored, 4, filter));
+        b.reverse();
+        assert_eq!(a, b, "{:?}", filter);
+    }
+}
+
+// Checks how each filter treats a hard edge between two greys: tent blurs it without leaving
+// their range, Lanczos overshoots on both sides, and Mitchell blurs like tent with only a slight
+// ring.
+#[test]
+fn hard_edge() {
+    let (lo, hi) = (64, 192);
+    let src = edge(48, 8, lo, hi);
+    let edge_of = |filter| row(&resample::downsample(&src, 4, filter));
+
+    let tent = edge_of(Filter::Tent);
+    assert!(tent.windows(2).all(|p| p[0] <= p[1]), "{:?}", tent);
+    assert!(tent[5] > lo && tent[6] < hi, "{:?}", tent);
+    assert!(tent.iter().all(|&c| c >= lo && c <= hi), "{:?}", tent);
+
+    let lanczos = edge_of(Filter::Lanczos);
+    let (l_min, l_max) = (*lanczos.iter().min().unwrap(), *lanczos.iter().max().unwrap());
+    assert!(l_min < lo && l_max > hi, "{:?}", lanczos);
+    assert!(lanczos[5] > lo && lanczos[6] < hi, "{:?}", lanczos);
+
+    let mitchell = edge_of(Filter::Mitchell);
+    let (m_min, m_max) = (*mitchell.iter().min().unwrap(), *mitchell.iter().max().unwrap());
+    assert!(mitchell[5] > lo && mitchell[6] < hi, "{:?}", mitchell);
+    assert!(m_min > l_min && m_min >= lo - 4 && m_max <= hi + 4,
+            "{:?} {:?}",
+            mitchell,
+            lanczos);
+}
@@ -0,0 +1,208 @@
+extern crate math;
+extern crate rustyrenderer;
+
+mod common;
+
+use std::io::Cursor;
+
+use math::Vec3f;
+use rustyrenderer::{draw, hdr, light, render, shader};
+use rustyrenderer::shader::Shader;
+use rustyrenderer::wavefront::Object;
+
+// A square facing the camera, filling the middle of the viewport.
+const QUAD: &'static str = "v -0.5 -0.5 0\nv 0.5 -0.5 0\nv 0.5 0.5 0\nv -0.5 0.5 0
+vt 0 0 0\nvt 1 0 0\nvt 1 1 0\nvt 0 1 0\nvn 0 0 1
+f 1/1/1 2/2/1 3/3/1\nf 1/1/1 3/3/1 4/4/1\n";
+
+const SIZE: usize = 16;
+
+// world looks at QUAD head on, lit from the camera so its center is at the peak of any highlight.
+fn world() -> shader::World {
+    let mut world = shader::World::new();
+    world.set_viewport(0, 0, SIZE, SIZE);
+    world.clear_lights();
+    world.add_light(light::Light::directional(Vec3f { x: 0., y: 0., z: 1. }));
+    world.look_at(Vec3f { x: 0., y: 0., z: 3. },
+                  Vec3f::zero(),
+                  Vec3f { x: 0., y: 1., z: 0. });
+    world
+}
+
+// center renders obj with shdr and returns the color at the middle of the image.
+fn center<S: Shader + Clone + Send>(world: &shader::World, obj: &Object, shdr: &S) -> draw::RGB {
+    let mut im = draw::Image::new(SIZE, SIZE);
+    let mut z_buffer = draw::DepthBuffer::new(SIZE, SIZE);
+    render::Renderer::new().draw(world, obj, shdr, &mut im, &mut z_buffer);
+    im.get(SIZE / 2, SIZE / 2)
+}
+
+// Checks brighter specular map texels are shinier, keeping the peak of the highlight but making it
+// fall off faster away from it.
+#[test]
+fn specular_map_sets_shininess() {
+    let mut dull = common::object(QUAD);
+    dull.set_specular_map(common::texture([20, 20, 20, 255]));
+    let mut shiny = common::object(QUAD);
+    shiny.set_specular_map(common::texture([200, 200, 200, 255]));
+    let highlight = |world: &shader::World, obj: &Object| {
+        let mut shdr = shader::PhongShader::new(obj);
+        shdr.set_coefficients(0., 0., 1.);
+        center(world, obj, &shdr).r as i32
+    };
+
+    // Lit from the camera the whole quad is at the peak.
+    let peak = world();
+    // The highlight is the mid grey diffuse color at full strength.
+    assert_eq!(highlight(&peak, &dull), 128);
+    assert!((highlight(&peak, &dull) - highlight(&peak, &shiny)).abs() <= 2);
+
+    // A little off the peak the tighter highlight is much dimmer.
+    let mut world = world();
+    world.clear_lights();
+    world.add_light(light::Light::directional(Vec3f { x: 0.4, y: 0., z: 1. }));
+    let (dull, shiny) = (highlight(&world, &dull), highlight(&world, &shiny));
+    assert!(shiny * 2 < dull, "dull {} shiny {}", dull, shiny);
+}
+
+// Checks a perfectly smooth PbrShader surface seen in the mirror direction of a light stays
+// finite, where the GGX distribution would otherwise divide zero by zero.
+#[test]
+fn pbr_zero_roughness_is_finite() {
+    let obj = common::object(QUAD);
+    // With identity transforms the normal is the normal map's, decoded as the object does, and a
+    // light mirrored about it from the camera puts the half vector right on it.
+    let n = Vec3f {
+        x: 128. / 255. * 2. - 1.,
+        y: 128. / 255. * 2. - 1.,
+        z: 1.,
+    }
+        .normalize();
+    let v = Vec3f { x: 0., y: 0., z: 1. };
+    let mut world = shader::World::new();
+    world.set_viewport(0, 0, SIZE, SIZE);
+    world.clear_lights();
+    world.add_light(light::Light::directional(n.scale(2. * math::dot(n, v)) - v));
+
+    let mut shdr = shader::PbrShader::new(&obj);
+    shdr.set_roughness(0.);
+    let mut im = hdr::HdrImage::new(SIZE, SIZE);
+    let mut z_buffer = draw::DepthBuffer::new(SIZE, SIZE);
+    render::Renderer::new().draw(&world, &obj, &shdr, &mut im, &mut z_buffer);
+    let c = im.get(SIZE / 2, SIZE / 2);
+    assert!(c.r.is_finite() && c.g.is_finite() && c.b.is_finite(), "{:?}", c);
+    assert!(im.buf.iter().all(|v| v.is_finite()));
+}
+
+// hdr_matches_clamped renders obj with shdr into both an Image and an HdrImage, checks light above
+// 1 survives in the latter and that clamping it gives the former.
+fn hdr_matches_clamped<S: Shader + Clone + Send>(world: &shader::World, obj: &Object, shdr: &S) {
+    let mut hdr_im = hdr::HdrImage::new(SIZE, SIZE);
+    let mut z_buffer = draw::DepthBuffer::new(SIZE, SIZE);
+    render::Renderer::new().draw(world, obj, shdr, &mut hdr_im, &mut z_buffer);
+    let c = hdr_im.get(SIZE / 2, SIZE / 2);
+    assert!(c.r > 1. && c.g > 1. && c.b > 1., "{:?}", c);
+
+    let im = hdr_im.resolve(hdr::ToneMap::Clamp, 1.);
+    let want = center(world, obj, shdr);
+    let got = im.get(SIZE / 2, SIZE / 2);
+    assert_eq!((got.r, got.g, got.b), (want.r, want.g, want.b));
+}
+
+// Checks the lit shaders light in linear space and return light above 1 to an HdrImage unclamped.
+#[test]
+fn shaders_keep_light_above_one() {
+    let mut world = shader::World::new();
+    world.set_viewport(0, 0, SIZE, SIZE);
+    world.clear_lights();
+    let mut bright = light::Light::directional(Vec3f { x: 0., y: 0., z: 1. });
+    bright.intensity = 8.;
+    world.add_light(bright);
+    world.look_at(Vec3f { x: 0., y: 0., z: 3. },
+                  Vec3f::zero(),
+                  Vec3f { x: 0., y: 1., z: 0. });
+    let obj = common::object(QUAD);
+
+    hdr_matches_clamped(&world, &obj, &shader::FlatShader::new(&obj));
+    hdr_matches_clamped(&world, &obj, &shader::GouraudShader::new(&obj));
+    hdr_matches_clamped(&world, &obj, &shader::PhongShader::new(&obj));
+    hdr_matches_clamped(&world, &obj, &shader::ToonShader::new(&obj));
+    hdr_matches_clamped(&world, &obj, &shader::PbrShader::new(&obj));
+}
+
+// alpha_tested renders obj, whose diffuse map has alpha 64, with shdr and checks the renderer's
+// alpha test discards it above that and blending weights it by that below.
+fn alpha_tested<S: Shader + Clone + Send>(world: &shader::World, obj: &Object, shdr: &S) {
+    let a = 64. / 255.;
+    let render = |alpha_test: f32, blend: render::Blend| {
+        let mut renderer = render::Renderer::new();
+        renderer.set_alpha_test(alpha_test);
+        renderer.set_blend(blend);
+        let mut im = hdr::HdrImage::new(SIZE, SIZE);
+        let mut z_buffer = draw::DepthBuffer::new(SIZE, SIZE);
+        renderer.draw(world, obj, shdr, &mut im, &mut z_buffer);
+        (im.get(SIZE / 2, SIZE / 2), z_buffer.get(SIZE / 2, SIZE / 2))
+    };
+
+    let (c, z) = render(0.5, render::Blend::Replace);
+    assert_eq!((c.r, c.a), (0., 0.));
+    assert_eq!(z, std::f32::MIN);
+
+    let (opaque, z) = render(0.2, render::Blend::Replace);
+    assert!(opaque.r > 0.);
+    assert!((opaque.a - a).abs() < 1e-6, "{:?}", opaque);
+    assert!(z > std::f32::MIN);
+
+    let (c, _) = render(0., render::Blend::Alpha);
+    assert!((c.r - opaque.r * a).abs() < 1e-6, "{:?} {:?}", c, opaque);
+
+    // 8-bit targets test alpha the same way.
+    let mut renderer = render::Renderer::new();
+    renderer.set_alpha_test(0.5);
+    let mut im = draw::Image::new(SIZE, SIZE);
+    let mut z_buffer = draw::DepthBuffer::new(SIZE, SIZE);
+    renderer.draw(world, obj, shdr, &mut im, &mut z_buffer);
+    assert_eq!(im.get(SIZE / 2, SIZE / 2).r, 0);
+    assert!(center(world, obj, shdr).r > 0);
+}
+
+// Checks every textured shader returns its diffuse map's alpha for the renderer to test and blend
+// with.
+#[test]
+fn alpha_test_and_blend() {
+    let world = world();
+    let mut obj = Object::new(common::texture([128, 128, 128, 64]),
+                              common::texture([255, 128, 128, 255]));
+    obj.parse(Cursor::new(QUAD)).unwrap();
+
+    alpha_tested(&world, &obj, &shader::FlatShader::new(&obj));
+    alpha_tested(&world, &obj, &shader::GouraudShader::new(&obj));
+    alpha_tested(&world, &obj, &shader::PhongShader::new(&obj));
+    alpha_tested(&world, &obj, &shader::ToonShader::new(&obj));
+    alpha_tested(&world, &obj, &shader::PbrShader::new(&obj));
+}
+
+// Checks ToonShader quantizes lighting into exactly the number of bands asked for, across a quad
+// whose normals turn from nearly edge on to the light to facing it.
+#[test]
+fn toon_bands() {
+    let obj = common::object("v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvt 0 0 0
+vn -1 0 0.05\nvn 0 0 1\nf 1/1/1 2/1/2 3/1/2\nf 1/1/1 3/1/2 4/1/1\n");
+    let mut world = shader::World::new();
+    world.set_viewport(0, 0, SIZE * 4, SIZE);
+    world.clear_lights();
+    world.add_light(light::Light::directional(Vec3f { x: 0., y: 0., z: 1. }));
+    for &bands in &[1, 2, 3, 5] {
+        let mut shdr = shader::ToonShader::new(&obj);
+        shdr.set_bands(bands);
+        shdr.set_ambient(0.);
+        let mut im = draw::Image::new(SIZE * 4, SIZE);
+        let mut z_buffer = draw::DepthBuffer::new(SIZE * 4, SIZE);
+        render::Renderer::new().draw(&world, &obj, &shdr, &mut im, &mut z_buffer);
+        let mut values: Vec<u8> = (0..SIZE * 4).map(|x| im.get(x, SIZE / 2).r).collect();
+        values.sort();
+        values.dedup();
+        assert_eq!(values.len(), bands, "{} bands: {:?}", bands, values);
+        assert!(values[0] > 0, "{} bands: {:?}", bands, values);
+    }
+}
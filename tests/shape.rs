@@ -0,0 +1,127 @@
+extern crate math;
+extern crate rustyrenderer;
+
+use math::Vec3f;
+use rustyrenderer::draw;
+use rustyrenderer::shape::{self, Fill, FillRule};
+
+// Pixels coverage is kept for, larger than any test draws in.
+const CLIP: (i64, i64, i64, i64) = (0, 0, 63, 63);
+
+fn p(x: f32, y: f32) -> Vec3f {
+    Vec3f { x: x, y: y, z: 0. }
+}
+
+// Checks midpoint ellipse outlines are connected, close to the true ellipse, and fill its area.
+#[test]
+fn ellipses() {
+    for &(rx, ry) in &[(10, 10), (12, 5), (3, 17), (1, 1), (0, 4)] {
+        let mut pts = Vec::new();
+        shape::ellipse(rx, ry, |x, y| pts.push((x, y)));
+        assert_eq!(pts[0], (0, ry));
+        assert_eq!(*pts.last().unwrap(), (rx, 0));
+        for w in pts.windows(2) {
+            let (dx, dy) = (w[1].0 - w[0].0, w[0].1 - w[1].1);
+            assert!(dx >= 0 && dy >= 0 && dx <= 1 && dy <= 1,
+                    "{}x{} gap {:?}",
+                    rx,
+                    ry,
+                    w);
+        }
+        if rx > 0 && ry > 0 {
+            for &(x, y) in &pts {
+                let (fx, fy) = (x as f32 / rx as f32, y as f32 / ry as f32);
+                let r = (fx * fx + fy * fy).sqrt();
+                let tolerance = 1. / rx.min(ry) as f32;
+                assert!((r - 1.).abs() <= tolerance, "{}x{} {},{} at {}", rx, ry, x, y, r);
+            }
+        }
+    }
+
+    let mut im = draw::Image::new(64, 64);
+    let white = draw::RGB {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+    im.fill_ellipse(32, 32, 20, 12, white);
+    let area = im.buf.iter().filter(|&&v| v == 255).count() / 3;
+    // The fill includes the outline pixels, reaching half a pixel further than the radii.
+    let expected = std::f32::consts::PI * 20.5 * 12.5;
+    assert!((area as f32 - expected).abs() < expected * 0.02,
+            "area {} expected {}",
+            area,
+            expected);
+}
+
+// Checks polygons fill pixels with their center inside, and the fill rules for a pentagram.
+#[test]
+fn polygons() {
+    let mut style = Fill::new(draw::RGB { r: 0, g: 0, b: 0 });
+    style.antialias = false;
+    let square = [p(2., 3.), p(10., 3.), p(10., 7.), p(2., 7.)];
+    let cov = shape::coverage(&square, &style, CLIP);
+    for y in 0..12 {
+        for x in 0..12 {
+            let want = if x >= 2 && x < 10 && y >= 3 && y < 7 { 1. } else { 0. };
+            assert_eq!(cov.get(x, y), want, "pixel {},{}", x, y);
+        }
+    }
+
+    // Anti-aliased coverage adds up to the area.
+    style.antialias = true;
+    let tri = [p(1.3, 1.1), p(20.7, 4.2), p(6.4, 15.9)];
+    let cov = shape::coverage(&tri, &style, CLIP);
+    let sum: f32 = cov.buf.iter().sum();
+    let area = ((tri[1].x - tri[0].x) * (tri[2].y - tri[0].y) -
+                (tri[2].x - tri[0].x) * (tri[1].y - tri[0].y))
+        .abs() / 2.;
+    assert!((sum - area).abs() < area * 0.02, "coverage {} area {}", sum, area);
+
+    let star: Vec<Vec3f> = (0..5)
+        .map(|i| {
+            let a = i as f32 * 4. * std::f32::consts::PI / 5.;
+            p(32. + 30. * a.sin(), 32. + 30. * a.cos())
+        })
+        .collect();
+    style.antialias = false;
+    style.rule = FillRule::EvenOdd;
+    let cov = shape::coverage(&star, &style, CLIP);
+    assert_eq!(cov.get(32, 32), 0.);
+    assert_eq!(cov.get(32, 55), 1.);
+    style.rule = FillRule::NonZero;
+    let cov = shape::coverage(&star, &style, CLIP);
+    assert_eq!(cov.get(32, 32), 1.);
+    assert_eq!(cov.get(32, 55), 1.);
+}
+
+// Checks polygons reaching far off the image fill it where they cover it, with coverage kept only
+// for the image.
+#[test]
+fn far_off_image() {
+    // A triangle covering the lower left half of the image, x + y < 16.
+    let tri = [p(-1e5, -1e5), p(1e5 + 16., -1e5), p(-1e5, 1e5 + 16.)];
+    for &antialias in &[false, true] {
+        let mut style = Fill::new(draw::RGB {
+            r: 255,
+            g: 255,
+            b: 255,
+        });
+        style.antialias = antialias;
+        let cov = shape::coverage(&tri, &style, (0, 0, 15, 15));
+        assert_eq!((cov.x, cov.y, cov.w, cov.h), (0, 0, 16, 16));
+
+        let mut im = draw::Image::new(16, 16);
+        im.fill_polygon(&tri, &style);
+        for y in 0..16 {
+            for x in 0..16 {
+                if x + y == 15 {
+                    // Centered on the edge.
+                    continue;
+                }
+                let want = if x + y < 15 { 255 } else { 0 };
+                assert_eq!(im.get(x, y).r, want, "antialias {} pixel {},{}", antialias, x, y);
+            }
+        }
+    }
+}
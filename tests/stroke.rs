@@ -0,0 +1,87 @@
+extern crate math;
+extern crate rustyrenderer;
+
+use math::{Vec2i, Vec3f};
+use rustyrenderer::draw;
+use rustyrenderer::stroke::{self, LineCap, Stroke};
+
+// Pixels coverage is kept for, larger than any test draws in.
+const CLIP: (i64, i64, i64, i64) = (0, 0, 63, 63);
+
+fn p(x: f32, y: f32) -> Vec3f {
+    Vec3f { x: x, y: y, z: 0. }
+}
+
+// Checks lines include both end points, for Image::line and anti-aliased lines along pixel centers.
+#[test]
+fn end_points_included() {
+    let white = draw::RGB {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+    let mut im = draw::Image::new(8, 8);
+    im.line(&Vec2i { x: 2, y: 1 }, &Vec2i { x: 6, y: 1 }, white);
+    im.line_aa(&p(2.5, 3.5), &p(6.5, 3.5), white);
+    im.line(&Vec2i { x: 4, y: 5 }, &Vec2i { x: 4, y: 5 }, white);
+    for &y in &[1, 3] {
+        for x in 0..8 {
+            let want = if x >= 2 && x <= 6 { 255 } else { 0 };
+            assert_eq!(im.get(x, y).r, want, "pixel {},{}", x, y);
+        }
+    }
+    assert_eq!(im.get(4, 5).r, 255);
+}
+
+// Checks a wide stroke with square caps covers the rectangle it outlines, and dashes leave gaps.
+#[test]
+fn wide_and_dashed() {
+    let mut style = Stroke::new(draw::RGB { r: 0, g: 0, b: 0 });
+    style.width = 4.;
+    style.cap = LineCap::Square;
+    style.antialias = false;
+    let cov = stroke::coverage(&[p(4., 10.), p(20., 10.)], &style, CLIP);
+    for y in 0..20 {
+        for x in 0..30 {
+            let want = if x >= 2 && x < 22 && y >= 8 && y < 12 { 1. } else { 0. };
+            assert_eq!(cov.get(x, y), want, "pixel {},{}", x, y);
+        }
+    }
+
+    style.width = 1.;
+    style.cap = LineCap::Butt;
+    style.dashes = vec![4., 4.];
+    let cov = stroke::coverage(&[p(0.5, 0.5), p(32.5, 0.5)], &style, CLIP);
+    let covered: Vec<bool> = (0..32).map(|x| cov.get(x, 0) > 0.).collect();
+    for x in 0..32 {
+        // Dash ends are included, so each dash covers one pixel more than its length.
+        assert_eq!(covered[x], x % 8 <= 4, "pixel {}", x);
+    }
+}
+
+// Checks lines reaching far off the image are drawn where they cross it, with coverage kept only
+// for the image.
+#[test]
+fn far_off_image() {
+    let far = [p(-1e5, -1e5), p(1e5, 1e5)];
+    for &width in &[1., 3.] {
+        let mut style = Stroke::new(draw::RGB { r: 0, g: 0, b: 0 });
+        style.width = width;
+        style.join = stroke::LineJoin::Round;
+        let cov = stroke::coverage(&far, &style, (0, 0, 15, 15));
+        assert!(cov.w <= 16 && cov.h <= 16, "{}x{} coverage", cov.w, cov.h);
+
+        let white = draw::RGB {
+            r: 255,
+            g: 255,
+            b: 255,
+        };
+        let mut im = draw::Image::new(16, 16);
+        style.color = white;
+        im.stroke(&far, &style);
+        im.stroke_polygon(&[far[0], far[1], p(1e5, -1e5)], &style);
+        for i in 0..16 {
+            assert_eq!(im.get(i, i).r, 255, "width {} pixel {},{}", width, i, i);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+extern crate math;
+extern crate rustyrenderer;
+
+use math::Vec3f;
+use rustyrenderer::draw::{Filter, RGB, Sampler, Texture2D, Wrap};
+
+// 4x1 texture with texels red 0, 1, 2 and 3 from left to right.
+fn ramp() -> Texture2D {
+    Texture2D::new(4, 1, vec![0, 0, 0, 255, 1, 0, 0, 255, 2, 0, 0, 255, 3, 0, 0, 255])
+}
+
+// Red of the texel sampled with wrap at u, the center of texel x.
+fn red_at(tex: &Texture2D, wrap: Wrap, x: f32) -> u8 {
+    let sampler = Sampler {
+        filter: Filter::Nearest,
+        wrap: wrap,
+    };
+    let uv = Vec3f {
+        x: (x + 0.5) / 4.,
+        y: 0.5,
+        z: 0.,
+    };
+    tex.sample_with(&sampler, uv, Vec3f::zero(), Vec3f::zero()).r
+}
+
+// Checks every wrap mode picks the right texel either side of the texture, including negative
+// UVs.
+#[test]
+fn wrap_modes() {
+    let tex = ramp();
+    let border = RGB { r: 9, g: 9, b: 9 };
+    let cases = [(Wrap::Repeat, [2, 3, 0, 3, 0, 1]),
+                 (Wrap::MirroredRepeat, [1, 0, 0, 3, 3, 2]),
+                 (Wrap::ClampToEdge, [0, 0, 0, 3, 3, 3]),
+                 (Wrap::ClampToBorder(border), [9, 9, 0, 3, 9, 9])];
+    for &(wrap, want) in &cases {
+        let got: Vec<u8> = [-2., -1., 0., 3., 4., 5.]
+            .iter()
+            .map(|&x| red_at(&tex, wrap, x))
+            .collect();
+        assert_eq!(got, want.to_vec(), "{:?}", wrap);
+    }
+}
+
+// Checks sample is defined for UVs far outside [0, 1] and not finite.
+#[test]
+fn sample_any_uv() {
+    let tex = ramp();
+    for &u in &[-1e9, -3.7, 17.2, 1e9, std::f32::INFINITY, std::f32::NAN] {
+        let c = tex.sample(Vec3f { x: u, y: -u, z: 0. });
+        assert!(c.r < 4, "u {}", u);
+    }
+}
+
+// Checks every filter samples the texture, not garbage or a panic, for UVs and derivatives far
+// outside [0, 1] and not finite.
+#[test]
+fn filters_any_uv() {
+    let tex = Texture2D::new(4, 4, [7, 8, 9, 255].iter().cycle().take(64).cloned().collect());
+    let border = RGB { r: 1, g: 2, b: 3 };
+    let filters = [Filter::Nearest, Filter::Bilinear, Filter::Trilinear, Filter::Anisotropic(8)];
+    let wraps = [Wrap::Repeat,
+                 Wrap::MirroredRepeat,
+                 Wrap::ClampToEdge,
+                 Wrap::ClampToBorder(border)];
+    let inf = std::f32::INFINITY;
+    let values = [-inf, -1e30, -3e9, -3.7, 0.5, 17.2, 3e9, 1e30, inf, std::f32::NAN];
+    for &filter in &filters {
+        for &wrap in &wraps {
+            let sampler = Sampler {
+                filter: filter,
+                wrap: wrap,
+            };
+            for &u in &values {
+                for &v in &values {
+                    let uv = Vec3f { x: u, y: v, z: 0. };
+                    let d = Vec3f { x: v, y: u, z: 0. };
+                    let c = tex.sample_with(&sampler, uv, d, Vec3f { x: 0.01, y: 0., z: 0. });
+                    let got = (c.r, c.g, c.b);
+                    // Clamping to the border may blend the border in at the texture's edge.
+                    let ok = match wrap {
+                        Wrap::ClampToBorder(_) => {
+                            got.0 >= 1 && got.0 <= 7 && got.1 == got.0 + 1 && got.2 == got.0 + 2
+                        }
+                        _ => got == (7, 8, 9),
+                    };
+                    assert!(ok, "{:?} {:?} at {},{}: {:?}", filter, wrap, u, v, got);
+                }
+            }
+        }
+    }
+}